@@ -0,0 +1,181 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Fields, Ident, Result};
+use crate::options;
+
+struct Options {
+    name: Option<Ident>,
+    consistent: bool,
+}
+
+fn parse_options(input: &DeriveInput) -> Result<Options> {
+    let mut options = Options { name: None, consistent: false };
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("atomiq")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                options.name = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("consistent") {
+                options.consistent = true;
+                Ok(())
+            } else if meta.path.is_ident("crate") {
+                options::skip(&meta)
+            } else {
+                Err(meta.error("unknown AtomicStruct option, expected `name`, `consistent` or `crate`."))
+            }
+        })?;
+    }
+    Ok(options)
+}
+
+pub(crate) fn derive_atomic_struct(input: DeriveInput) -> Result<TokenStream> {
+    let options = parse_options(&input)?;
+    let krate = &options::parse_common(&input.attrs)?.krate;
+    let vis = &input.vis;
+    let name = &input.ident;
+    let atomic = options.name.unwrap_or_else(|| format_ident!("Atomic{}", name));
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), "AtomicStruct cannot be derived for generic types."));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            fields => {
+                return Err(Error::new(fields.span(), "AtomicStruct can only be derived for structs with named fields."));
+            }
+        },
+        _ => {
+            return Err(Error::new(Span::call_site(), "AtomicStruct can only be derived for structs with named fields."));
+        }
+    };
+
+    let idents: Vec<_> = fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    // Writes to the fields must go through the seqlock in consistent mode.
+    let field_vis: Vec<_> = fields
+        .iter()
+        .map(|field| if options.consistent { quote!() } else { field.vis.to_token_stream() })
+        .collect();
+
+    // Wraps writes and multi-field reads in the seqlock in consistent mode.
+    let write = |body: TokenStream| {
+        if options.consistent {
+            quote!(self.seqlock.write(|| #body))
+        } else {
+            body
+        }
+    };
+    let read = |body: TokenStream| {
+        if options.consistent {
+            quote!(self.seqlock.read(|| #body))
+        } else {
+            body
+        }
+    };
+    let (seqlock_field, seqlock_init) = if options.consistent {
+        (quote!(seqlock: #krate::seqlock::SeqLock,), quote!(seqlock: #krate::seqlock::SeqLock::new(),))
+    } else {
+        (quote!(), quote!())
+    };
+
+    let methods = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let suffix = ident.to_string().trim_start_matches("r#").to_owned();
+        let load = format_ident!("load_{}", suffix);
+        let store = format_ident!("store_{}", suffix);
+        let fetch_add = format_ident!("fetch_add_{}", suffix);
+        let fetch_sub = format_ident!("fetch_sub_{}", suffix);
+        let load_doc = format!("Loads the `{}` field.", suffix);
+        let store_doc = format!("Stores the `{}` field.", suffix);
+        let fetch_add_doc = format!("Adds to the `{}` field, returning its previous value.", suffix);
+        let fetch_sub_doc = format!("Subtracts from the `{}` field, returning its previous value.", suffix);
+        let store_body = write(quote!(self.#ident.store(value, ordering)));
+        let fetch_add_body = write(quote!(self.#ident.fetch_add(value, ordering)));
+        let fetch_sub_body = write(quote!(self.#ident.fetch_sub(value, ordering)));
+        quote! {
+            #[doc = #load_doc]
+            #vis fn #load(&self, ordering: #krate::Ordering) -> #ty {
+                self.#ident.load(ordering)
+            }
+
+            #[doc = #store_doc]
+            #vis fn #store(&self, value: #ty, ordering: #krate::Ordering) {
+                #store_body
+            }
+
+            #[doc = #fetch_add_doc]
+            #vis fn #fetch_add(&self, value: #ty, ordering: #krate::Ordering) -> #ty
+            where
+                for<'a> #ty: #krate::IntAtomizable,
+            {
+                #fetch_add_body
+            }
+
+            #[doc = #fetch_sub_doc]
+            #vis fn #fetch_sub(&self, value: #ty, ordering: #krate::Ordering) -> #ty
+            where
+                for<'a> #ty: #krate::IntAtomizable,
+            {
+                #fetch_sub_body
+            }
+        }
+    });
+
+    let snapshot_body = read(quote!(#name { #(#idents: self.#idents.load(ordering),)* }));
+    let store_all_body = write(quote!({ #(self.#idents.store(value.#idents, ordering);)* }));
+
+    let struct_doc = format!("Atomic version of [`{}`], with an atomic for each field.", name);
+    let snapshot_doc = if options.consistent {
+        "Loads all fields at once. Concurrent writes are never partially observed."
+    } else {
+        "Loads all fields one by one. Concurrent writes may be partially observed, use \
+         `#[atomiq(consistent)]` to prevent it."
+    };
+
+    Ok(quote! {
+        #[doc = #struct_doc]
+        #vis struct #atomic {
+            #(#field_vis #idents: #krate::Atomic<#types>,)*
+            #seqlock_field
+        }
+
+        impl #atomic {
+            /// Creates a new atomic struct with the given value.
+            #vis fn new(value: #name) -> Self {
+                Self {
+                    #(#idents: #krate::Atomic::from(value.#idents),)*
+                    #seqlock_init
+                }
+            }
+
+            #(#methods)*
+
+            #[doc = #snapshot_doc]
+            #vis fn snapshot(&self, ordering: #krate::Ordering) -> #name {
+                #snapshot_body
+            }
+
+            /// Stores all fields.
+            #vis fn store_all(&self, value: #name, ordering: #krate::Ordering) {
+                #store_all_body
+            }
+
+            /// Stores the default value in all fields.
+            #vis fn reset(&self, ordering: #krate::Ordering)
+            where
+                for<'a> #name: ::core::default::Default,
+            {
+                self.store_all(::core::default::Default::default(), ordering)
+            }
+        }
+
+        impl ::core::convert::From<#name> for #atomic {
+            fn from(value: #name) -> Self {
+                Self::new(value)
+            }
+        }
+    })
+}
@@ -0,0 +1,330 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::meta::ParseNestedMeta;
+use syn::{DataStruct, DeriveInput, Error, Fields, Ident, Index, LitInt, Member, Path, Result, Type};
+use crate::{fields, options};
+
+/// Returns whether the struct should be packed as a bitfield rather than as a newtype.
+pub(crate) fn is_bitfield(input: &DeriveInput, data: &DataStruct) -> bool {
+    let has_options = |attrs: &[syn::Attribute]| attrs.iter().any(|attr| attr.path().is_ident("atomiq"));
+    let mut packed = data.fields.iter().filter(|field| !fields::is_marker(field));
+    // Errors are reported by `derive_bitfield`.
+    let has_repr = !matches!(parse_repr(input), Ok(None));
+    packed.clone().count() > 1 || has_repr || packed.any(|field| has_options(&field.attrs))
+}
+
+/// Returns the width of the primitive atom types known to the derive.
+fn primitive_bits(ty: &Type) -> Option<u32> {
+    let Type::Path(ty) = ty else {
+        return None;
+    };
+    if ty.qself.is_some() {
+        return None;
+    }
+    let ident = ty.path.get_ident()?.to_string();
+    match ident.as_str() {
+        "bool" => Some(1),
+        "u8" | "i8" => Some(8),
+        "u16" | "i16" => Some(16),
+        "u32" | "i32" => Some(32),
+        "u64" | "i64" => Some(64),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `repr = u64` option.
+pub(crate) fn parse_repr_value(meta: &ParseNestedMeta) -> Result<Ident> {
+    let ident: Ident = meta.value()?.parse()?;
+    if !["u8", "u16", "u32", "u64", "usize"].iter().any(|ty| ident == ty) {
+        return Err(Error::new(ident.span(), "Atomizable repr must be an unsigned integer type."));
+    }
+    Ok(ident)
+}
+
+/// Returns the smallest unsigned integer type fitting `total` bits, or `u64` if unknown.
+pub(crate) fn default_repr(total: Option<u32>) -> Result<Ident> {
+    let repr = match total {
+        Some(0..=8) => "u8",
+        Some(9..=16) => "u16",
+        Some(17..=32) => "u32",
+        Some(33..=64) | None => "u64",
+        Some(_) => return Err(Error::new(Span::call_site(), "Atomizable fields do not fit in 64 bits.")),
+    };
+    Ok(Ident::new(repr, Span::call_site()))
+}
+
+fn parse_repr(input: &DeriveInput) -> Result<Option<Ident>> {
+    let mut repr = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("atomiq")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("repr") {
+                repr = Some(parse_repr_value(&meta)?);
+                Ok(())
+            } else if options::is_common(&meta) {
+                options::skip(&meta)
+            } else {
+                Err(meta.error("unknown Atomizable option, expected `repr`."))
+            }
+        })?;
+    }
+    Ok(repr)
+}
+
+fn parse_bits(field: &syn::Field) -> Result<Option<LitInt>> {
+    let mut bits = None;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("atomiq")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("bits") {
+                let lit: LitInt = meta.value()?.parse()?;
+                let value: u32 = lit.base10_parse()?;
+                if value == 0 || value > 64 {
+                    return Err(Error::new(lit.span(), "Atomizable field bits must be between 1 and 64."));
+                }
+                bits = Some(lit);
+                Ok(())
+            } else {
+                Err(meta.error("unknown Atomizable field option, expected `bits`."))
+            }
+        })?;
+    }
+    Ok(bits)
+}
+
+pub(crate) struct Field<'a> {
+    pub(crate) member: Member,
+    /// The suffix of the generated accessors.
+    pub(crate) suffix: String,
+    pub(crate) ty: &'a Type,
+    krate: &'a Path,
+    /// The width as an expression, and as a number if known to the derive.
+    pub(crate) width: TokenStream,
+    pub(crate) known_width: Option<u32>,
+    explicit: bool,
+}
+
+impl Field<'_> {
+    /// Returns a statement inserting `value` into `bits` at `offset`.
+    pub(crate) fn pack(&self, offset: &TokenStream, value: TokenStream) -> TokenStream {
+        let ty = self.ty;
+        let width = &self.width;
+        let krate = self.krate;
+        quote! {
+            bits = #krate::bitfield::insert(
+                bits,
+                #offset,
+                #width,
+                #krate::bitfield::BitField::to_bits(<#ty as #krate::Atomizable>::pack(#value)),
+            );
+        }
+    }
+
+    /// Returns an expression extracting the field from `bits` at `offset`.
+    pub(crate) fn unpack(&self, offset: &TokenStream) -> TokenStream {
+        let ty = self.ty;
+        let width = &self.width;
+        let krate = self.krate;
+        quote! {
+            <#ty as #krate::Atomizable>::unpack(
+                <<#ty as #krate::Atomizable>::Atom as #krate::bitfield::BitField>::from_bits(
+                    #krate::bitfield::extract(bits, #offset, #width),
+                    #width,
+                ),
+            )
+        }
+    }
+}
+
+fn member(index: usize, field: &syn::Field) -> Member {
+    match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index::from(index)),
+    }
+}
+
+/// Returns the fields packed into the atom, skipping markers.
+pub(crate) fn packed_fields<'a>(fields: &'a Fields, krate: &'a Path) -> Result<Vec<Field<'a>>> {
+    fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| !fields::is_marker(field))
+        .map(|(index, field)| {
+            let bits = parse_bits(field)?;
+            let ty = &field.ty;
+            let suffix = match &field.ident {
+                Some(ident) => ident.to_string().trim_start_matches("r#").to_owned(),
+                None => index.to_string(),
+            };
+            let (width, known_width) = match &bits {
+                Some(bits) => (quote!(#bits), Some(bits.base10_parse()?)),
+                None => (
+                    quote!(<<#ty as #krate::Atomizable>::Atom as #krate::bitfield::BitField>::BITS),
+                    primitive_bits(ty),
+                ),
+            };
+            Ok(Field { member: member(index, field), suffix, ty, krate, width, known_width, explicit: bits.is_some() })
+        })
+        .collect()
+}
+
+/// Returns the marker fields, which are recreated with `Default::default()` on unpack.
+pub(crate) fn markers(fields: &Fields) -> Vec<Member> {
+    fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| fields::is_marker(field))
+        .map(|(index, field)| member(index, field))
+        .collect()
+}
+
+/// Returns the offset of each field, the sum of `start` and the widths of the fields before it.
+pub(crate) fn offsets(fields: &[Field], start: &TokenStream) -> Vec<TokenStream> {
+    (0..fields.len())
+        .map(|index| {
+            let widths = fields[..index].iter().map(|field| &field.width);
+            quote!(#start #(+ #widths)*)
+        })
+        .collect()
+}
+
+/// Returns assertions that the explicit widths fit the types of the fields.
+pub(crate) fn width_checks(fields: &[Field], owner: &str, krate: &Path) -> TokenStream {
+    let checks = fields.iter().filter(|field| field.explicit).map(|field| {
+        let ty = field.ty;
+        let width = &field.width;
+        let message = format!("Field `{}` of `{}` is wider than its type.", field.suffix, owner);
+        quote! {
+            ::core::assert!(#width <= <<#ty as #krate::Atomizable>::Atom as #krate::bitfield::BitField>::BITS, #message);
+        }
+    });
+    quote!(#(#checks)*)
+}
+
+pub(crate) fn derive_bitfield(input: &DeriveInput, data: &DataStruct, krate: &Path) -> Result<TokenStream> {
+    let name = &input.ident;
+    let vis = &input.vis;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), "Atomizable bitfields cannot be generic."));
+    }
+    let fields = packed_fields(&data.fields, krate)?;
+    if fields.is_empty() {
+        return Err(Error::new(Span::call_site(), "Atomizable bitfields must have at least one field."));
+    }
+
+    let repr = match parse_repr(input)? {
+        Some(repr) => repr,
+        None => default_repr(fields.iter().map(|field| field.known_width).sum())?,
+    };
+
+    let offsets = offsets(&fields, &quote!(0));
+    let widths: Vec<_> = fields.iter().map(|field| &field.width).collect();
+    let members: Vec<_> = fields.iter().map(|field| &field.member).collect();
+    let too_wide = width_checks(&fields, &name.to_string(), krate);
+    let too_many = format!("Fields of `{}` do not fit in `{}`.", name, repr);
+
+    let values = fields.iter().zip(&offsets).map(|(field, offset)| field.unpack(offset));
+    let markers = markers(&data.fields);
+    let packs = fields.iter().zip(&offsets).map(|(field, offset)| {
+        let member = &field.member;
+        field.pack(offset, quote!(self.#member))
+    });
+
+    let trait_name = format_ident!("Atomic{}Fields", name);
+    let trait_doc = format!("Per-field access to an atomic [`{}`].", name);
+    let methods = fields.iter().map(|field| {
+        let ty = field.ty;
+        let member = &field.member;
+        let load = format_ident!("load_{}", field.suffix);
+        let store = format_ident!("store_{}", field.suffix);
+        let update = format_ident!("update_field_{}", field.suffix);
+        let load_doc = format!("Loads the `{}` field.", field.suffix);
+        let store_doc = format!("Stores the `{}` field, leaving the other fields unchanged.", field.suffix);
+        let update_doc = format!(
+            "Updates the `{}` field with a compare-and-swap loop, leaving the other fields unchanged.\n\n\
+             Works like `Atomic::fetch_update`, returning the \
+             previous value of the whole struct.",
+            field.suffix,
+        );
+        let declarations = quote! {
+            #[doc = #load_doc]
+            fn #load(&self, ordering: #krate::Ordering) -> #ty;
+
+            #[doc = #store_doc]
+            fn #store(&self, value: #ty, ordering: #krate::Ordering);
+
+            #[doc = #update_doc]
+            fn #update<F>(
+                &self,
+                set_ordering: #krate::Ordering,
+                fetch_ordering: #krate::Ordering,
+                f: F,
+            ) -> ::core::result::Result<#name, #name>
+            where
+                F: ::core::ops::FnMut(#ty) -> ::core::option::Option<#ty>;
+        };
+        let definitions = quote! {
+            fn #load(&self, ordering: #krate::Ordering) -> #ty {
+                self.load(ordering).#member
+            }
+
+            fn #store(&self, value: #ty, ordering: #krate::Ordering) {
+                let atom = <#ty as #krate::Atomizable>::pack(value);
+                let _ = self.#update(ordering, #krate::Ordering::Relaxed, |_| {
+                    ::core::option::Option::Some(<#ty as #krate::Atomizable>::unpack(atom))
+                });
+            }
+
+            fn #update<F>(
+                &self,
+                set_ordering: #krate::Ordering,
+                fetch_ordering: #krate::Ordering,
+                mut f: F,
+            ) -> ::core::result::Result<#name, #name>
+            where
+                F: ::core::ops::FnMut(#ty) -> ::core::option::Option<#ty>,
+            {
+                self.fetch_update(set_ordering, fetch_ordering, |mut value| {
+                    value.#member = f(value.#member)?;
+                    ::core::option::Option::Some(value)
+                })
+            }
+        };
+        (declarations, definitions)
+    });
+    let (declarations, definitions): (Vec<_>, Vec<_>) = methods.unzip();
+
+    Ok(quote! {
+        const _: () = {
+            ::core::assert!(0 #(+ #widths)* <= <#repr as #krate::bitfield::BitField>::BITS, #too_many);
+            #too_wide
+        };
+
+        impl #krate::Atomizable for #name {
+            type Atom = #repr;
+
+            fn pack(self) -> Self::Atom {
+                let mut bits = 0u64;
+                #(#packs)*
+                bits as #repr
+            }
+
+            fn unpack(atom: Self::Atom) -> Self {
+                let bits = atom as u64;
+                Self {
+                    #(#members: #values,)*
+                    #(#markers: ::core::default::Default::default(),)*
+                }
+            }
+        }
+
+        #[doc = #trait_doc]
+        #vis trait #trait_name {
+            #(#declarations)*
+        }
+
+        impl #trait_name for #krate::Atomic<#name> {
+            #(#definitions)*
+        }
+    })
+}
@@ -0,0 +1,172 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::{DataEnum, DeriveInput, Error, Ident, Meta, Path, Result, Token};
+use crate::{bitfield, options};
+
+/// The integer reprs with an atomic type.
+const INTEGER_REPRS: &[&str] = &["u8", "u16", "u32", "u64", "usize", "i8", "i16", "i32", "i64", "isize"];
+
+/// Finds the integer repr among all `#[repr(...)]` attributes of the enum.
+pub(crate) fn parse_repr(input: &DeriveInput) -> Result<Ident> {
+    let mut repr: Option<Ident> = None;
+    let mut found_attr = false;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        found_attr = true;
+        let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for meta in metas {
+            let Some(ident) = meta.path().get_ident() else {
+                continue;
+            };
+            if ident == "u128" || ident == "i128" {
+                return Err(Error::new(ident.span(), format!("`{}` has no atomic type.", ident)));
+            }
+            if !INTEGER_REPRS.iter().any(|ty| ident == ty) {
+                continue;
+            }
+            if repr.is_some() {
+                return Err(Error::new(ident.span(), "conflicting integer reprs."));
+            }
+            repr = Some(ident.clone());
+        }
+    }
+
+    match repr {
+        Some(repr) => Ok(repr),
+        None if found_attr => Err(Error::new_spanned(
+            input.attrs.iter().find(|attr| attr.path().is_ident("repr")),
+            "Atomizable enums need an integer repr, such as `#[repr(u8)]`.",
+        )),
+        None => Err(Error::new(
+            Span::call_site(),
+            "Atomizable can only be derived for enums with an explicit repr attribute.",
+        )),
+    }
+}
+
+/// What `unpack` does with a value that is not a discriminant of the enum.
+pub(crate) enum Invalid {
+    Panic,
+    Default(Ident),
+    UnreachableUnchecked,
+}
+
+pub(crate) struct EnumOptions {
+    pub(crate) invalid: Invalid,
+    /// Whether the enum opted into `BitAtomizable` and `IntAtomizable`.
+    pub(crate) allow_ops: bool,
+    /// The integer type of a tagged enum.
+    pub(crate) repr: Option<Ident>,
+}
+
+pub(crate) fn parse_options(input: &DeriveInput, data: &DataEnum) -> Result<EnumOptions> {
+    let mut options = EnumOptions { invalid: Invalid::Panic, allow_ops: false, repr: None };
+    let mut invalid_set = false;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("atomiq")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("invalid") || meta.path.is_ident("default") {
+                if invalid_set {
+                    return Err(meta.error("the invalid value strategy is already set."));
+                }
+                invalid_set = true;
+                let ident: Ident = meta.value()?.parse()?;
+                options.invalid = if meta.path.is_ident("default") {
+                    if !data.variants.iter().any(|variant| variant.ident == ident) {
+                        return Err(Error::new(ident.span(), "the default must be a variant of the enum."));
+                    }
+                    Invalid::Default(ident)
+                } else if ident == "panic" {
+                    Invalid::Panic
+                } else if ident == "unreachable_unchecked" {
+                    Invalid::UnreachableUnchecked
+                } else {
+                    return Err(Error::new(
+                        ident.span(),
+                        "expected `panic` or `unreachable_unchecked`, or use `default = Variant`.",
+                    ));
+                };
+                Ok(())
+            } else if meta.path.is_ident("allow_ops") {
+                options.allow_ops = true;
+                Ok(())
+            } else if meta.path.is_ident("repr") {
+                options.repr = Some(bitfield::parse_repr_value(&meta)?);
+                Ok(())
+            } else if options::is_common(&meta) {
+                options::skip(&meta)
+            } else {
+                Err(meta.error("unknown Atomizable enum option, expected `invalid`, `default`, `allow_ops` or `repr`."))
+            }
+        })?;
+    }
+    Ok(options)
+}
+
+/// Generates the expression handling an invalid `value`, described as `what` in panics.
+pub(crate) fn fallback(name: &Ident, invalid: &Invalid, what: &str, value: TokenStream) -> TokenStream {
+    match invalid {
+        Invalid::Panic => {
+            let message = format!("invalid {} {{}} for `{}`", what, name);
+            quote!(::core::panic!(#message, #value))
+        }
+        Invalid::Default(variant) => quote!(Self::#variant),
+        Invalid::UnreachableUnchecked => quote! {
+            // SAFETY: The enum opted into treating invalid values as undefined behaviour.
+            unsafe { ::core::hint::unreachable_unchecked() }
+        },
+    }
+}
+
+/// Implements `Atomizable` for an enum of unit variants, matching the atom against the
+/// discriminants when unpacking.
+pub(crate) fn derive_enum(input: &DeriveInput, data: &DataEnum, options: &EnumOptions, krate: &Path) -> Result<TokenStream> {
+    let name = &input.ident;
+    let repr = parse_repr(input)?;
+    let variants: Vec<_> = data.variants.iter().map(|variant| &variant.ident).collect();
+    let discriminants: Vec<_> = (0..variants.len()).map(|index| format_ident!("DISCRIMINANT_{}", index)).collect();
+
+    let fallback = fallback(name, &options.invalid, "discriminant", quote!(atom));
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #krate::Atomizable for #name #ty_generics #where_clause {
+            type Atom = #repr;
+
+            fn pack(self) -> Self::Atom {
+                self as #repr
+            }
+
+            fn unpack(atom: Self::Atom) -> Self {
+                #(const #discriminants: #repr = #name::#variants as #repr;)*
+
+                match atom {
+                    #(#discriminants => Self::#variants,)*
+                    _ => #fallback,
+                }
+            }
+        }
+    })
+}
+
+/// Rejects deriving `BitAtomizable` or `IntAtomizable` for enums that did not opt in, either with
+/// `allow_ops` or with `opted_in` set by the `bit` and `int` options.
+pub(crate) fn check_ops(input: &DeriveInput, data: &DataEnum, derive: &str, opted_in: bool) -> Result<()> {
+    let options = parse_options(input, data)?;
+    if !options.allow_ops && !opted_in {
+        return Err(Error::new(
+            Span::call_site(),
+            format!(
+                "{} on an enum can produce values that are not variants; add `#[atomiq(allow_ops)]` to opt in.",
+                derive,
+            ),
+        ));
+    }
+    if let Invalid::UnreachableUnchecked = options.invalid {
+        return Err(Error::new(
+            Span::call_site(),
+            format!("{} cannot be derived for enums with `invalid = unreachable_unchecked`.", derive),
+        ));
+    }
+    Ok(())
+}
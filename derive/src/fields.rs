@@ -0,0 +1,25 @@
+use syn::{Field, Type};
+use crate::options;
+
+/// Returns whether the field is a zero-sized marker, either `PhantomData`/`PhantomPinned` or a
+/// field with `#[atomiq(skip)]`.
+///
+/// Markers are not packed into the atom and are recreated with `Default::default()` on unpack.
+pub(crate) fn is_marker(field: &Field) -> bool {
+    if let Type::Path(ty) = &field.ty {
+        if let Some(segment) = ty.path.segments.last() {
+            if segment.ident == "PhantomData" || segment.ident == "PhantomPinned" {
+                return true;
+            }
+        }
+    }
+
+    field.attrs.iter().filter(|attr| attr.path().is_ident("atomiq")).any(|attr| {
+        let mut skip = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            skip |= meta.path.is_ident("skip");
+            options::skip(&meta)
+        });
+        skip
+    })
+}
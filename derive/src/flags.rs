@@ -0,0 +1,385 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Fields, Ident, Result};
+use crate::options;
+
+/// Converts `ReadWrite` or `read_write` into `READ_WRITE`.
+fn constant_name(ident: &Ident) -> Ident {
+    let name = ident.to_string();
+    let mut constant = String::with_capacity(name.len() + 4);
+    let mut previous_lower = false;
+    for c in name.trim_start_matches("r#").chars() {
+        if c.is_uppercase() && previous_lower {
+            constant.push('_');
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        constant.extend(c.to_uppercase());
+    }
+    Ident::new(&constant, ident.span())
+}
+
+struct Options {
+    name: Option<Ident>,
+    repr: Option<Ident>,
+}
+
+fn parse_options(input: &DeriveInput) -> Result<Options> {
+    let mut options = Options { name: None, repr: None };
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("atomiq")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                options.name = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("repr") {
+                let repr: Ident = meta.value()?.parse()?;
+                if !["u8", "u16", "u32", "u64", "usize"].iter().any(|ty| repr == ty) {
+                    return Err(Error::new(repr.span(), "AtomicFlags repr must be an unsigned integer type."));
+                }
+                options.repr = Some(repr);
+                Ok(())
+            } else if meta.path.is_ident("crate") {
+                options::skip(&meta)
+            } else {
+                Err(meta.error("unknown AtomicFlags option, expected `name`, `repr` or `crate`."))
+            }
+        })?;
+    }
+    Ok(options)
+}
+
+pub(crate) fn derive_atomic_flags(input: DeriveInput) -> Result<TokenStream> {
+    let options = parse_options(&input)?;
+    let krate = &options::parse_common(&input.attrs)?.krate;
+    let vis = &input.vis;
+    let name = &input.ident;
+    let flags = options.name.unwrap_or_else(|| format_ident!("{}Flags", name));
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), "AtomicFlags cannot be derived for generic types."));
+    }
+
+    // The source identifiers of the flags, in bit order.
+    let (sources, conversions) = match &input.data {
+        Data::Enum(data) => {
+            for variant in &data.variants {
+                if !variant.fields.is_empty() {
+                    return Err(Error::new(
+                        variant.fields.span(),
+                        "AtomicFlags can only be derived for enums with only unit variants.",
+                    ));
+                }
+                if let Some((_, discriminant)) = &variant.discriminant {
+                    return Err(Error::new(
+                        discriminant.span(),
+                        "AtomicFlags variants are assigned bits in order and cannot have explicit discriminants.",
+                    ));
+                }
+            }
+
+            let sources: Vec<_> = data.variants.iter().map(|variant| &variant.ident).collect();
+            let constants = sources.iter().map(|ident| constant_name(ident));
+            let conversions = quote! {
+                impl ::core::convert::From<#name> for #flags {
+                    fn from(flag: #name) -> Self {
+                        match flag {
+                            #(#name::#sources => Self::#constants,)*
+                        }
+                    }
+                }
+            };
+            (sources, conversions)
+        }
+        Data::Struct(data) => {
+            let Fields::Named(fields) = &data.fields else {
+                return Err(Error::new(
+                    data.fields.span(),
+                    "AtomicFlags can only be derived for structs with named `bool` fields.",
+                ));
+            };
+            for field in &fields.named {
+                let is_bool = matches!(&field.ty, syn::Type::Path(ty) if ty.qself.is_none() && ty.path.is_ident("bool"));
+                if !is_bool {
+                    return Err(Error::new(field.ty.span(), "AtomicFlags struct fields must be `bool`."));
+                }
+            }
+
+            let sources: Vec<_> = fields.named.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+            let constants: Vec<_> = sources.iter().map(|ident| constant_name(ident)).collect();
+            let conversions = quote! {
+                impl ::core::convert::From<#name> for #flags {
+                    fn from(value: #name) -> Self {
+                        let mut flags = Self::empty();
+                        #(flags.set(Self::#constants, value.#sources);)*
+                        flags
+                    }
+                }
+
+                impl ::core::convert::From<#flags> for #name {
+                    fn from(flags: #flags) -> Self {
+                        Self {
+                            #(#sources: flags.contains(#flags::#constants),)*
+                        }
+                    }
+                }
+            };
+            (sources, conversions)
+        }
+        Data::Union(_) => {
+            return Err(Error::new(Span::call_site(), "AtomicFlags can only be derived for enums and structs."));
+        }
+    };
+
+    let count = sources.len();
+    let repr = match options.repr {
+        Some(repr) => repr,
+        None => Ident::new(
+            match count {
+                0..=8 => "u8",
+                9..=16 => "u16",
+                17..=32 => "u32",
+                33..=64 => "u64",
+                _ => return Err(Error::new(Span::call_site(), "AtomicFlags supports at most 64 flags.")),
+            },
+            Span::call_site(),
+        ),
+    };
+
+    let constants: Vec<_> = sources.iter().map(|ident| constant_name(ident)).collect();
+    let bits = 0..count;
+    let docs = sources.iter().map(|ident| format!("The `{}` flag.", ident));
+    let names = constants.iter().map(|ident| ident.to_string());
+    let too_many = format!("Too many flags for `{}`.", repr);
+    let type_doc = format!("A set of [`{}`] flags.", name);
+
+    Ok(quote! {
+        #[doc = #type_doc]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        #[repr(transparent)]
+        #vis struct #flags(#repr);
+
+        const _: () = ::core::assert!(#count <= #repr::BITS as usize, #too_many);
+
+        impl #flags {
+            #(
+                #[doc = #docs]
+                pub const #constants: Self = Self(1 << #bits);
+            )*
+
+            /// Returns the set with no flags.
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            /// Returns the set with all flags.
+            pub const fn all() -> Self {
+                Self(0 #(| Self::#constants.0)*)
+            }
+
+            /// Returns the raw bits of the set.
+            pub const fn bits(self) -> #repr {
+                self.0
+            }
+
+            /// Converts raw bits into a set, or returns `None` if any bit is not a known flag.
+            pub const fn from_bits(bits: #repr) -> ::core::option::Option<Self> {
+                if bits & !Self::all().0 == 0 {
+                    ::core::option::Option::Some(Self(bits))
+                } else {
+                    ::core::option::Option::None
+                }
+            }
+
+            /// Converts raw bits into a set, dropping any bits that are not known flags.
+            pub const fn from_bits_truncate(bits: #repr) -> Self {
+                Self(bits & Self::all().0)
+            }
+
+            /// Returns whether no flags are set.
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            /// Returns whether all flags are set.
+            pub const fn is_all(self) -> bool {
+                self.0 == Self::all().0
+            }
+
+            /// Returns whether all flags of `other` are also in `self`.
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Returns whether any flag of `other` is also in `self`.
+            pub const fn intersects(self, other: Self) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            /// Adds the flags of `other`.
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            /// Removes the flags of `other`.
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            /// Flips the flags of `other`.
+            pub fn toggle(&mut self, other: Self) {
+                self.0 ^= other.0;
+            }
+
+            /// Adds or removes the flags of `other`.
+            pub fn set(&mut self, other: Self, value: bool) {
+                if value {
+                    self.insert(other);
+                } else {
+                    self.remove(other);
+                }
+            }
+
+            /// Returns the flags in either set.
+            pub const fn union(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+
+            /// Returns the flags in both sets.
+            pub const fn intersection(self, other: Self) -> Self {
+                Self(self.0 & other.0)
+            }
+
+            /// Returns the flags in `self` but not in `other`.
+            pub const fn difference(self, other: Self) -> Self {
+                Self(self.0 & !other.0)
+            }
+
+            /// Returns the flags in exactly one of the sets.
+            pub const fn symmetric_difference(self, other: Self) -> Self {
+                Self(self.0 ^ other.0)
+            }
+
+            /// Returns the flags that are not in `self`.
+            pub const fn complement(self) -> Self {
+                Self(!self.0 & Self::all().0)
+            }
+
+            /// Returns an iterator over the individual flags in the set.
+            pub fn iter(self) -> impl ::core::iter::Iterator<Item = Self> {
+                self.iter_names().map(|(_, flag)| flag)
+            }
+
+            /// Returns an iterator over the names and values of the individual flags in the set.
+            pub fn iter_names(self) -> impl ::core::iter::Iterator<Item = (&'static str, Self)> {
+                <Self as #krate::Flags>::FLAGS
+                    .iter()
+                    .copied()
+                    .filter(move |&(_, flag)| self.contains(flag))
+            }
+        }
+
+        impl #krate::Atomizable for #flags {
+            type Atom = #repr;
+
+            fn pack(self) -> Self::Atom {
+                self.0
+            }
+
+            fn unpack(atom: Self::Atom) -> Self {
+                Self(atom)
+            }
+        }
+
+        impl #krate::BitAtomizable for #flags {}
+
+        impl #krate::Flags for #flags {
+            const FLAGS: &'static [(&'static str, Self)] = &[#((#names, Self::#constants)),*];
+
+            fn contains(self, other: Self) -> bool {
+                Self::contains(self, other)
+            }
+
+            fn complement(self) -> Self {
+                Self::complement(self)
+            }
+        }
+
+        impl ::core::fmt::Debug for #flags {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                #krate::flags::fmt_flags(::core::stringify!(#flags), *self, f)
+            }
+        }
+
+        impl ::core::ops::BitOr for #flags {
+            type Output = Self;
+
+            fn bitor(self, other: Self) -> Self {
+                self.union(other)
+            }
+        }
+
+        impl ::core::ops::BitOrAssign for #flags {
+            fn bitor_assign(&mut self, other: Self) {
+                self.insert(other);
+            }
+        }
+
+        impl ::core::ops::BitAnd for #flags {
+            type Output = Self;
+
+            fn bitand(self, other: Self) -> Self {
+                self.intersection(other)
+            }
+        }
+
+        impl ::core::ops::BitAndAssign for #flags {
+            fn bitand_assign(&mut self, other: Self) {
+                *self = self.intersection(other);
+            }
+        }
+
+        impl ::core::ops::BitXor for #flags {
+            type Output = Self;
+
+            fn bitxor(self, other: Self) -> Self {
+                self.symmetric_difference(other)
+            }
+        }
+
+        impl ::core::ops::BitXorAssign for #flags {
+            fn bitxor_assign(&mut self, other: Self) {
+                self.toggle(other);
+            }
+        }
+
+        impl ::core::ops::Sub for #flags {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                self.difference(other)
+            }
+        }
+
+        impl ::core::ops::SubAssign for #flags {
+            fn sub_assign(&mut self, other: Self) {
+                self.remove(other);
+            }
+        }
+
+        impl ::core::ops::Not for #flags {
+            type Output = Self;
+
+            fn not(self) -> Self {
+                self.complement()
+            }
+        }
+
+        impl ::core::iter::FromIterator<#flags> for #flags {
+            fn from_iter<I: ::core::iter::IntoIterator<Item = #flags>>(iter: I) -> Self {
+                iter.into_iter().fold(Self::empty(), Self::union)
+            }
+        }
+
+        #conversions
+    })
+}
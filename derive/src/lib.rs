@@ -1,128 +1,375 @@
 extern crate proc_macro;
 
+mod bitfield;
+mod enums;
+mod fields;
+mod tagged;
+mod flags;
+mod options;
+mod atomic_struct;
+mod model_test;
+
 use proc_macro::{TokenStream};
-use syn::{parse_macro_input, DeriveInput, Ident};
-use quote::{quote, quote_spanned, ToTokens};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{parse_macro_input, Data, DataStruct, DeriveInput, Fields};
+use quote::{format_ident, quote, quote_spanned};
 use syn::spanned::Spanned;
+use options::CommonOptions;
 
-#[proc_macro_derive(Atomizable)]
+/// Derives `Atomizable` for a newtype struct, an enum, or a struct packing several fields into
+/// one integer.
+///
+/// Options for all types are given with `#[atomiq(...)]`:
+/// - `bit` --- also implements `BitAtomizable`.
+/// - `int` --- also implements `BitAtomizable` and `IntAtomizable`.
+/// - `crate = "path"` --- the path of the `atomiq` crate, for when it is re-exported. This also
+///   applies to the other derives.
+///
+/// Generic parameters and where clauses are kept on the generated impls. `PhantomData` and
+/// `PhantomPinned` fields, and fields marked with `#[atomiq(skip)]`, are not packed and are
+/// recreated with `Default::default()` when unpacking.
+///
+/// Structs with more than one field, or with `#[atomiq(...)]` options, are packed as bitfields.
+/// Options are given with `#[atomiq(...)]`:
+/// - `repr = u64` --- on the struct, the integer type of the atom, by default the smallest that
+///   fits.
+/// - `bits = N` --- on a field, the width of the field, by default the width of its atom.
+///
+/// Bitfields also get an `Atomic{Name}Fields` trait with per-field `load_x`, `store_x` and
+/// `update_field_x` methods on `Atomic<Name>`. See `atomiq::bitfield` for more information.
+///
+/// On enums, `unpack` matches the value against the discriminants. What happens with a value
+/// that is not a discriminant is set with `#[atomiq(...)]`:
+/// - `invalid = panic` --- panics. (default)
+/// - `default = Variant` --- unpacks into the given variant.
+/// - `invalid = unreachable_unchecked` --- assumes it never happens, which is undefined behaviour
+///   if it does.
+///
+/// Enums with fields, or with `#[atomiq(repr = u64)]`, are packed as tagged unions: the index of
+/// the variant in the lowest bits, followed by the fields of the variant, packed like a bitfield.
+/// The invalid value options above apply to unknown tags.
+///
+/// `BitAtomizable` and `IntAtomizable` can only be derived for enums with `#[atomiq(allow_ops)]`,
+/// `bit` or `int`, as the operations can produce values that are not discriminants.
+#[proc_macro_derive(Atomizable, attributes(atomiq))]
 pub fn derive_atomizable(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
-    let name = &input.ident;
+    atomizable(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    let (field_type, pack_line, unpack_line) = if let syn::Data::Struct(ref data) = input.data {
-        let field = if let syn::Fields::Named(ref fields) = data.fields {
-            if fields.named.len() != 1 {
-                return TokenStream::from(quote_spanned! { fields.span() =>
-                    compile_error!("Atomizable can only be derived for structs with a single field.");
-                });
-            }
-            fields.named.get(0).unwrap()
-        } else if let syn::Fields::Unnamed(ref fields) = data.fields {
-            if fields.unnamed.len() != 1 {
-                return TokenStream::from(quote_spanned! { fields.span() =>
-                    compile_error!("Atomizable can only be derived for structs with a single field.");
-                });
+fn atomizable(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let options = options::parse_common(&input.attrs)?;
+    let krate = &options.krate;
+
+    let mut expanded = match &input.data {
+        Data::Struct(data) if bitfield::is_bitfield(input, data) => bitfield::derive_bitfield(input, data, krate)?,
+        Data::Struct(data) => newtype(input, data, krate)?,
+        Data::Enum(data) => {
+            let enum_options = enums::parse_options(input, data)?;
+            if tagged::is_tagged(data, &enum_options) {
+                tagged::derive_tagged(input, data, &enum_options, krate)?
+            } else {
+                enums::derive_enum(input, data, &enum_options, krate)?
             }
-            fields.unnamed.get(0).unwrap()
-        } else {
-            return TokenStream::from(quote! {
-                compile_error!("Atomizable can only be derived for structs with a single field.");
-            });
-        };
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(Span::call_site(), "Atomizable can only be derived for structs and enums."));
+        }
+    };
 
-        let field_name = field
-            .ident
-            .as_ref();
+    if options.bit || options.int {
+        expanded.extend(operations(input, &options, "BitAtomizable", "BitAtom")?);
+    }
+    if options.int {
+        expanded.extend(operations(input, &options, "IntAtomizable", "IntAtom")?);
+    }
+
+    Ok(expanded)
+}
+
+/// Implements `Atomizable` for a struct with a single packed field.
+fn newtype(input: &DeriveInput, data: &DataStruct, krate: &syn::Path) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
 
-        if let Some(ref field_name) = field_name {
-            (field.ty.to_token_stream(), quote! { self.#field_name }, quote! { #name { #field_name: atom } })
+    if let Fields::Unit = data.fields {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "Atomizable can only be derived for structs with a single field.",
+        ));
+    }
+
+    let (index, field) = single_field(data).ok_or_else(|| {
+        syn::Error::new(data.fields.span(), "Atomizable can only be derived for structs with a single field.")
+    })?;
+
+    let ty = &field.ty;
+    let member = match &field.ident {
+        Some(ident) => syn::Member::Named(ident.clone()),
+        None => syn::Member::Unnamed(syn::Index::from(index)),
+    };
+    let values = data.fields.iter().map(|other| {
+        if std::ptr::eq(other, field) {
+            quote!(<#ty as #krate::Atomizable>::unpack(atom))
         } else {
-            (field.ty.to_token_stream(), quote! { self.0 }, quote! { #name(atom) })
+            quote!(::core::default::Default::default())
         }
-    } else if let syn::Data::Enum(ref data) = input.data {
-        let repr_attr = input.attrs.iter().find(|attr| {
-            attr.meta.path().is_ident("repr")
-        });
-
-        if repr_attr.is_none() {
-            return TokenStream::from(quote! {
-                compile_error!(
-                    "Atomizable can only be derived for enums with an explicit repr attribute."
-                );
-            });
+    });
+    let unpack_line = match &data.fields {
+        Fields::Named(_) => {
+            let members = data.fields.iter().map(|field| &field.ident);
+            quote! { Self { #(#members: #values),* } }
         }
-
-        let repr_ident: Ident = repr_attr.unwrap().parse_args().unwrap();
-
-        let fielded_variants = data.variants.iter().filter(|variant| !variant.fields.is_empty())
-            .map(|variant| {
-                quote_spanned! {variant.fields.span()=>
-                    compile_error!(
-                        "Atomizable can only be derived for enums with only unit variants."
-                    );
-                }
-            })
-            .collect::<Vec<_>>();
-        if !fielded_variants.is_empty() {
-            return TokenStream::from(quote! {
-                #(#fielded_variants)*
-            });
-        }
-
-        (
-            repr_ident.to_token_stream(),
-            quote!(self as #repr_ident),
-            quote!(unsafe { core::mem::transmute(atom) }),
-        )
-    } else {
-        return TokenStream::from(quote! {
-            compile_error!("Atomizable can only be derived for structs and enums.");
-        });
+        _ => quote! { Self(#(#values),*) },
     };
 
-    let expanded = quote! {
-        impl ::atomiq::Atomizable for #name {
-            type Atom = #field_type;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let where_clause = with_predicate(where_clause, quote!(#ty: #krate::Atomizable));
+
+    Ok(quote! {
+        impl #impl_generics #krate::Atomizable for #name #ty_generics #where_clause {
+            type Atom = <#ty as #krate::Atomizable>::Atom;
             
             fn pack(self) -> Self::Atom {
-                #pack_line
+                <#ty as #krate::Atomizable>::pack(self.#member)
             }
             
             fn unpack(atom: Self::Atom) -> Self {
                 #unpack_line
             }
         }
+    })
+}
+
+/// Returns the only field of the struct that is not a marker.
+fn single_field(data: &DataStruct) -> Option<(usize, &syn::Field)> {
+    let mut packed = data.fields.iter().enumerate().filter(|(_, field)| !fields::is_marker(field));
+    match (packed.next(), packed.next()) {
+        (Some(field), None) => Some(field),
+        _ => None,
+    }
+}
+
+/// Implements `BitAtomizable` or `IntAtomizable`, checking that the atom supports the operations.
+fn operations(input: &DeriveInput, options: &CommonOptions, derive: &str, atom: &str) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let krate = &options.krate;
+    let derive_trait = format_ident!("{}", derive);
+    let atom_trait = format_ident!("{}", atom);
+
+    if let Data::Enum(data) = &input.data {
+        enums::check_ops(input, data, derive, options.bit || options.int)?;
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let where_clause = with_predicate(
+        where_clause,
+        quote!(Self: #krate::Atomizable<Atom: #krate::#atom_trait>),
+    );
+
+    // Points errors about the atom at the field it comes from, when there is one.
+    let span = match &input.data {
+        Data::Struct(data) if !bitfield::is_bitfield(input, data) => {
+            single_field(data).map_or(name.span(), |(_, field)| field.ty.span())
+        }
+        _ => name.span(),
+    };
+    let check = if input.generics.params.is_empty() {
+        quote_spanned! {span=>
+            const _: fn() = || {
+                fn check<T: #krate::#atom_trait>() {}
+                check::<<#name as #krate::Atomizable>::Atom>();
+            };
+        }
+    } else {
+        quote!()
     };
 
-    TokenStream::from(expanded)
+    Ok(quote! {
+        #check
+
+        impl #impl_generics #krate::#derive_trait for #name #ty_generics #where_clause {}
+    })
 }
 
-#[proc_macro_derive(BitAtomizable)]
+/// Derives `BitAtomizable` for a type whose `Atom` supports bitwise operations.
+///
+/// Also available as `#[derive(Atomizable)]` with `#[atomiq(bit)]`.
+#[proc_macro_derive(BitAtomizable, attributes(atomiq))]
 pub fn derive_bit_atomizable(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
-    let name = &input.ident;
-
-    let expanded = quote! {
-        impl ::atomiq::BitAtomizable for #name {}
-    };
-    
-    TokenStream::from(expanded)
+    options::parse_common(&input.attrs)
+        .and_then(|options| operations(&input, &options, "BitAtomizable", "BitAtom"))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
 
-#[proc_macro_derive(IntAtomizable)]
+/// Derives `IntAtomizable` for a type whose `Atom` supports integer operations.
+///
+/// Also available as `#[derive(Atomizable)]` with `#[atomiq(int)]`, which derives
+/// `BitAtomizable` as well.
+#[proc_macro_derive(IntAtomizable, attributes(atomiq))]
 pub fn derive_int_atomizable(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
+    options::parse_common(&input.attrs)
+        .and_then(|options| operations(&input, &options, "IntAtomizable", "IntAtom"))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `AffineAtomizable`, offsetting the type by a delta type.
+///
+/// The delta type is given with `#[atomiq(delta = Type)]` and must be `Atomizable` with the same
+/// `Atom`. For newtype structs, it defaults to the type of the field.
+#[proc_macro_derive(AffineAtomizable, attributes(atomiq))]
+pub fn derive_affine_atomizable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    affine(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn affine(input: &DeriveInput) -> syn::Result<TokenStream2> {
     let name = &input.ident;
+    let options = options::parse_common(&input.attrs)?;
+    let krate = &options.krate;
+
+    let delta = match (&options.delta, &input.data) {
+        (Some(delta), _) => delta.clone(),
+        (None, Data::Struct(data)) if !bitfield::is_bitfield(input, data) && single_field(data).is_some() => {
+            single_field(data).unwrap().1.ty.clone()
+        }
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "AffineAtomizable needs a delta type, given with `#[atomiq(delta = Type)]`.",
+            ));
+        }
+    };
 
-    let expanded = quote! {
-        impl ::atomiq::IntAtomizable for #name {}
+    if let Data::Enum(data) = &input.data {
+        enums::check_ops(input, data, "AffineAtomizable", options.int)?;
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // Bounds on concrete types are checked by the impl itself, and spelling them out makes the
+    // compiler overflow while normalizing `Self::Atom`.
+    let where_clause = if input.generics.params.is_empty() {
+        quote!(#where_clause)
+    } else {
+        with_predicate(
+            where_clause,
+            quote! {
+                Self: #krate::Atomizable<Atom: #krate::IntAtom>,
+                #delta: #krate::Atomizable<Atom = <Self as #krate::Atomizable>::Atom>
+            },
+        )
     };
-    
-    TokenStream::from(expanded)
+
+    Ok(quote! {
+        impl #impl_generics #krate::AffineAtomizable for #name #ty_generics #where_clause {
+            type Delta = #delta;
+
+            fn pack_delta(delta: Self::Delta) -> Self::Atom {
+                <#delta as #krate::Atomizable>::pack(delta)
+            }
+        }
+    })
+}
+
+/// Appends a predicate to a possibly missing where clause.
+fn with_predicate(where_clause: Option<&syn::WhereClause>, predicate: TokenStream2) -> TokenStream2 {
+    match where_clause {
+        Some(where_clause) if !where_clause.predicates.is_empty() => {
+            let predicates = where_clause.predicates.iter();
+            quote! { where #(#predicates,)* #predicate }
+        }
+        _ => quote! { where #predicate },
+    }
+}
+
+/// Derives a typed set of flags.
+///
+/// On an enum of unit variants, each variant becomes a flag. On a struct of `bool` fields, each
+/// field becomes a flag. The generated type is named after the input with a `Flags` suffix and
+/// gets a constant for each flag, set operations, and `Atomizable`, `BitAtomizable` and
+/// `atomiq::Flags` implementations.
+///
+/// Options are given with `#[atomiq(...)]`:
+/// - `name = Ident` --- the name of the generated type.
+/// - `repr = u32` --- the integer type of the set, by default the smallest that fits.
+#[proc_macro_derive(AtomicFlags, attributes(atomiq))]
+pub fn derive_atomic_flags(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    flags::derive_atomic_flags(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives an atomic version of a struct, with an atomic for each field.
+///
+/// The generated type is named after the input with an `Atomic` prefix and gets per-field
+/// `load_x`, `store_x`, `fetch_add_x` and `fetch_sub_x` methods, `snapshot`, `store_all`, `reset`
+/// and a `From` implementation. Every field must be `Atomizable`, and `IntAtomizable` for its
+/// `fetch_add_x` and `fetch_sub_x` methods.
+///
+/// Options are given with `#[atomiq(...)]`:
+/// - `name = Ident` --- the name of the generated type.
+/// - `consistent` --- guards writes with a `SeqLock`, so that `snapshot` never observes a write
+///   partially. The fields are then only accessible through the methods.
+#[proc_macro_derive(AtomicStruct, attributes(atomiq))]
+pub fn derive_atomic_struct(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    atomic_struct::derive_atomic_struct(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Runs a test under the model checker when `loom` or `shuttle` is enabled, and repeatedly
+/// otherwise.
+///
+/// The test body is wrapped in the right model runner, and the iteration numbers are logged at
+/// the info level. With `shuttle`, the body is run under `iterations` random schedules. Without
+/// either, the body is simply called `iterations` times in a row on the test thread; it only runs
+/// concurrently with the threads it spawns itself, so repeating it gives races in those threads
+/// more chances to show up. The function must take no arguments and return nothing; other
+/// attributes such as `#[should_panic]` are kept.
+///
+/// Options are given as arguments:
+/// - `preemption_bound = N` --- with `loom`, the maximum number of thread preemptions to explore.
+/// - `max_branches = N` --- with `loom`, the maximum number of branches in one execution.
+/// - `iterations = N` --- without `loom`, how many schedules `shuttle` explores, or how many
+///   times to run the test body in a row. (default: 100)
+/// - `crate = "path"` --- the path of the `atomiq` crate, for when it is re-exported.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+///
+/// #[atomiq::test(preemption_bound = 3, iterations = 100)]
+/// fn increments() {
+///     let counter: Atomic<u32> = Atomic::from(0);
+///     counter.fetch_add(1, Ordering::Relaxed);
+///     assert_eq!(counter.load(Ordering::Relaxed), 1);
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = model_test::Options::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(item as syn::ItemFn);
+
+    model_test::expand(options, function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
 
 #[cfg(test)]
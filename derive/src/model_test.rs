@@ -0,0 +1,93 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+use syn::{Error, ItemFn, LitInt, LitStr, Path, Result, ReturnType};
+
+/// How many times a test is run without `loom`, unless set with `iterations = N`.
+const DEFAULT_ITERATIONS: usize = 100;
+
+pub(crate) struct Options {
+    krate: Path,
+    preemption_bound: Option<LitInt>,
+    max_branches: Option<LitInt>,
+    iterations: Option<LitInt>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { krate: syn::parse_quote!(::atomiq), preemption_bound: None, max_branches: None, iterations: None }
+    }
+}
+
+impl Options {
+    pub(crate) fn parse(&mut self, meta: ParseNestedMeta) -> Result<()> {
+        let positive = |meta: &ParseNestedMeta| -> Result<LitInt> {
+            let lit: LitInt = meta.value()?.parse()?;
+            if lit.base10_parse::<usize>()? == 0 {
+                return Err(Error::new(lit.span(), "atomiq::test options must be positive."));
+            }
+            Ok(lit)
+        };
+        if meta.path.is_ident("preemption_bound") {
+            self.preemption_bound = Some(positive(&meta)?);
+        } else if meta.path.is_ident("max_branches") {
+            self.max_branches = Some(positive(&meta)?);
+        } else if meta.path.is_ident("iterations") {
+            self.iterations = Some(positive(&meta)?);
+        } else if meta.path.is_ident("crate") {
+            let path: LitStr = meta.value()?.parse()?;
+            self.krate = path.parse()?;
+        } else {
+            return Err(meta.error(
+                "unknown atomiq::test option, expected `preemption_bound`, `max_branches`, `iterations` or `crate`.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn expand(options: Options, function: ItemFn) -> Result<TokenStream> {
+    let ItemFn { attrs, vis, sig, block } = function;
+
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(asyncness.span(), "atomiq::test functions cannot be async."));
+    }
+    if !sig.inputs.is_empty() {
+        return Err(Error::new(sig.inputs.span(), "atomiq::test functions cannot take arguments."));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(sig.generics.span(), "atomiq::test functions cannot be generic."));
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        return Err(Error::new(ty.span(), "atomiq::test functions cannot return a value."));
+    }
+
+    let krate = &options.krate;
+    let ident = &sig.ident;
+    let mut settings = Vec::new();
+    if let Some(bound) = &options.preemption_bound {
+        settings.push(quote!(.preemption_bound(#bound)));
+    }
+    if let Some(branches) = &options.max_branches {
+        settings.push(quote!(.max_branches(#branches)));
+    }
+    let iterations = match &options.iterations {
+        Some(iterations) => quote!(#iterations),
+        None => quote!(#DEFAULT_ITERATIONS),
+    };
+    settings.push(quote!(.iterations(#iterations)));
+
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis #sig {
+            let builder = #krate::ModelBuilder::new()
+                .name(::core::concat!(::core::module_path!(), "::", ::core::stringify!(#ident)))
+                .log_level(::core::option::Option::Some(#krate::LogLevel::Info))
+                #(#settings)*;
+            #krate::try_init_model_with(builder, move || #block);
+        }
+    })
+}
+
@@ -0,0 +1,50 @@
+use syn::meta::ParseNestedMeta;
+use syn::{Attribute, LitStr, Path, Result, Type};
+
+/// Options shared by all derives, given with `#[atomiq(...)]` on the type.
+pub(crate) struct CommonOptions {
+    /// The path of the `atomiq` crate, `::atomiq` by default.
+    pub(crate) krate: Path,
+    /// Whether to also implement `BitAtomizable`.
+    pub(crate) bit: bool,
+    /// Whether to also implement `BitAtomizable` and `IntAtomizable`.
+    pub(crate) int: bool,
+    /// The delta type of `AffineAtomizable`.
+    pub(crate) delta: Option<Type>,
+}
+
+/// Parses the common options, ignoring options specific to a derive.
+pub(crate) fn parse_common(attrs: &[Attribute]) -> Result<CommonOptions> {
+    let mut options = CommonOptions { krate: syn::parse_quote!(::atomiq), bit: false, int: false, delta: None };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("atomiq")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                let path: LitStr = meta.value()?.parse()?;
+                options.krate = path.parse()?;
+            } else if meta.path.is_ident("bit") {
+                options.bit = true;
+            } else if meta.path.is_ident("int") {
+                options.int = true;
+            } else if meta.path.is_ident("delta") {
+                options.delta = Some(meta.value()?.parse()?);
+            } else {
+                skip(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+/// Returns whether the option is one of the common options.
+pub(crate) fn is_common(meta: &ParseNestedMeta) -> bool {
+    ["crate", "bit", "int", "delta"].iter().any(|option| meta.path.is_ident(option))
+}
+
+/// Skips the value of an option, if it has one.
+pub(crate) fn skip(meta: &ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    }
+    Ok(())
+}
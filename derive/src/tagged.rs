@@ -0,0 +1,116 @@
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{DataEnum, DeriveInput, Error, Path, Result};
+use crate::bitfield;
+use crate::enums::{self, EnumOptions};
+
+/// Returns whether the enum should be packed as a tagged union rather than as a discriminant.
+pub(crate) fn is_tagged(data: &DataEnum, options: &EnumOptions) -> bool {
+    options.repr.is_some() || data.variants.iter().any(|variant| !variant.fields.is_empty())
+}
+
+pub(crate) fn derive_tagged(input: &DeriveInput, data: &DataEnum, options: &EnumOptions, krate: &Path) -> Result<TokenStream> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), "Atomizable tagged enums cannot be generic."));
+    }
+    if data.variants.is_empty() {
+        return Err(Error::new(Span::call_site(), "Atomizable cannot be derived for empty enums."));
+    }
+
+    // The tag is the index of the variant, stored in the lowest bits.
+    let tag_bits = usize::BITS - (data.variants.len() - 1).leading_zeros();
+    let tag_width = quote!(#tag_bits);
+
+    let variants = data
+        .variants
+        .iter()
+        .map(|variant| Ok((variant, bitfield::packed_fields(&variant.fields, krate)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let repr = match &options.repr {
+        Some(repr) => repr.clone(),
+        None => {
+            let totals = variants.iter().map(|(_, fields)| {
+                fields.iter().map(|field| field.known_width).sum::<Option<u32>>().map(|total| tag_bits + total)
+            });
+            bitfield::default_repr(totals.collect::<Option<Vec<_>>>().map(|totals| totals.into_iter().max().unwrap_or(0)))?
+        }
+    };
+
+    let mut checks = Vec::new();
+    let mut pack_arms = Vec::new();
+    let mut unpack_arms = Vec::new();
+    for (tag, (variant, fields)) in variants.iter().enumerate() {
+        let ident = &variant.ident;
+        let owner = format!("{}::{}", name, ident);
+        let tag = Literal::u64_unsuffixed(tag as u64);
+
+        let widths = fields.iter().map(|field| &field.width);
+        let too_many = format!("Variant `{}` does not fit in `{}`.", owner, repr);
+        let too_wide = bitfield::width_checks(fields, &owner, krate);
+        checks.push(quote! {
+            ::core::assert!(#tag_bits #(+ #widths)* <= <#repr as #krate::bitfield::BitField>::BITS, #too_many);
+            #too_wide
+        });
+
+        let offsets = bitfield::offsets(fields, &tag_width);
+        let members: Vec<_> = fields.iter().map(|field| &field.member).collect();
+        let bindings: Vec<_> = (0..fields.len()).map(|index| format_ident!("field_{}", index)).collect();
+        let packs = fields.iter().zip(&offsets).zip(&bindings).map(|((field, offset), binding)| {
+            field.pack(offset, quote!(#binding))
+        });
+        let tagged = quote!(#krate::bitfield::insert(0, 0, #tag_width, #tag));
+        let body = if fields.is_empty() {
+            tagged
+        } else {
+            quote! {{
+                let mut bits = #tagged;
+                #(#packs)*
+                bits
+            }}
+        };
+        pack_arms.push(quote! {
+            Self::#ident { #(#members: #bindings,)* .. } => #body,
+        });
+
+        let values = fields.iter().zip(&offsets).map(|(field, offset)| field.unpack(offset));
+        let markers = bitfield::markers(&variant.fields);
+        unpack_arms.push(quote! {
+            #tag => Self::#ident {
+                #(#members: #values,)*
+                #(#markers: ::core::default::Default::default(),)*
+            },
+        });
+    }
+
+    let fallback = enums::fallback(name, &options.invalid, "tag", quote!(tag));
+
+    Ok(quote! {
+        const _: () = {
+            #(#checks)*
+        };
+
+        impl #krate::Atomizable for #name {
+            type Atom = #repr;
+
+            fn pack(self) -> Self::Atom {
+                let bits = match self {
+                    #(#pack_arms)*
+                };
+                bits as #repr
+            }
+
+            fn unpack(atom: Self::Atom) -> Self {
+                let bits = atom as u64;
+                let tag = #krate::bitfield::extract(bits, 0, #tag_width);
+                match tag {
+                    #(#unpack_arms)*
+                    _ => #fallback,
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,15 @@
+#[atomiq::test(iterations = 0)]
+fn zero_iterations() {}
+
+#[atomiq::test(seed = 1)]
+fn unknown_option() {}
+
+#[atomiq::test]
+fn with_argument(value: u32) {}
+
+#[atomiq::test]
+fn with_result() -> Result<(), ()> {
+    Ok(())
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use atomiq::prelude::*;
+
+#[derive(AtomicStruct)]
+struct Pair(u32, u32);
+
+#[derive(AtomicStruct)]
+#[atomiq(consistent)]
+struct Status {
+    ready: bool,
+}
+
+fn main() {
+    let status = AtomicStatus::new(Status { ready: false });
+    status.fetch_add_ready(true, Ordering::Relaxed);
+}
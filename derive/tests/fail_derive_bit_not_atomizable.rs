@@ -0,0 +1,6 @@
+use atomiq_derive::BitAtomizable;
+
+#[derive(BitAtomizable)]
+struct TestStruct(u8);
+
+fn main() {}
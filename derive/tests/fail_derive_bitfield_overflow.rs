@@ -0,0 +1,11 @@
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable)]
+#[atomiq(repr = u16)]
+struct TestStruct {
+    #[atomiq(bits = 12)]
+    a: u16,
+    b: u8,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable)]
+struct TestStruct {
+    #[atomiq(bits = 9)]
+    a: u8,
+    b: bool,
+}
+
+fn main() {}
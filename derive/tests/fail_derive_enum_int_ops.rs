@@ -0,0 +1,10 @@
+use atomiq_derive::{Atomizable, IntAtomizable};
+
+#[derive(Atomizable, IntAtomizable)]
+#[repr(u8)]
+enum TestEnum {
+    A,
+    B,
+}
+
+fn main() {}
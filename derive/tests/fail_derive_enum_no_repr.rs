@@ -1,5 +1,3 @@
-#![cfg(not(clippy))]
-
 use atomiq_derive::Atomizable;
 
 #[derive(Atomizable)]
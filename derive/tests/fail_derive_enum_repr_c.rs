@@ -1,13 +1,10 @@
-#![cfg(not(clippy))]
-
 use atomiq_derive::Atomizable;
 
 #[derive(Atomizable)]
-#[repr(u8)]
+#[repr(C)]
 enum TestEnum {
     A,
-    B(u8),
-    C { value: u8 },
+    B,
 }
 
-fn main() {}
\ No newline at end of file
+fn main() {}
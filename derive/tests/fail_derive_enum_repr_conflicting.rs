@@ -0,0 +1,10 @@
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable)]
+#[repr(u8, u16)]
+enum TestEnum {
+    A,
+    B,
+}
+
+fn main() {}
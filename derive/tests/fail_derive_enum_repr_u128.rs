@@ -0,0 +1,10 @@
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable)]
+#[repr(u128)]
+enum TestEnum {
+    A,
+    B,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use atomiq_derive::AtomicFlags;
+
+#[derive(AtomicFlags)]
+struct TestFlags {
+    first: bool,
+    second: u8,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable)]
+#[atomiq(int)]
+struct TestStruct(*mut u8);
+
+fn main() {}
@@ -1,8 +0,0 @@
-#![cfg(not(clippy))]
-
-use atomiq_derive::Atomizable;
-
-#[derive(Atomizable)]
-struct TestStruct(i32, i32);
-
-fn main() {}
\ No newline at end of file
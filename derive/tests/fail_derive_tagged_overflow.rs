@@ -0,0 +1,11 @@
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable)]
+#[atomiq(repr = u32)]
+enum TestEnum {
+    A,
+    B(u16),
+    C { value: u32 },
+}
+
+fn main() {}
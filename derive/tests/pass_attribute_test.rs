@@ -0,0 +1,16 @@
+#[atomiq::test]
+fn plain() {
+    use atomiq::prelude::*;
+
+    let atomic: Atomic<u32> = Atomic::from(0);
+    atomic.fetch_add(1, Ordering::Relaxed);
+    assert_eq!(atomic.load(Ordering::Relaxed), 1);
+}
+
+#[atomiq::test(preemption_bound = 2, max_branches = 1000, iterations = 5)]
+#[should_panic]
+fn with_options() {
+    panic!("expected");
+}
+
+fn main() {}
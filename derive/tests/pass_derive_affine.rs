@@ -0,0 +1,34 @@
+use std::marker::PhantomData;
+
+use atomiq::prelude::*;
+use atomiq_derive::{AffineAtomizable, Atomizable};
+
+#[derive(Atomizable)]
+struct Ticks(u64);
+
+#[derive(Atomizable, AffineAtomizable, Debug, PartialEq)]
+#[atomiq(delta = Ticks)]
+struct Timestamp(u64);
+
+#[derive(Atomizable, AffineAtomizable, Debug, PartialEq)]
+struct Index {
+    value: usize,
+}
+
+#[derive(Atomizable, AffineAtomizable)]
+struct Id<T>(u32, PhantomData<T>);
+
+fn main() {
+    let now: Atomic<Timestamp> = Timestamp(100).atomize();
+    assert_eq!(now.fetch_add_delta(Ticks(20), Ordering::Relaxed), Timestamp(100));
+    assert_eq!(now.fetch_sub_delta(Ticks(5), Ordering::Relaxed), Timestamp(120));
+    assert_eq!(now.load(Ordering::Relaxed), Timestamp(115));
+
+    let index: Atomic<Index> = Index { value: 0 }.atomize();
+    index.fetch_add_delta(3, Ordering::Relaxed);
+    assert_eq!(index.load(Ordering::Relaxed), Index { value: 3 });
+
+    let id: Atomic<Id<Index>> = Id(1, PhantomData).atomize();
+    id.fetch_add_delta(1, Ordering::Relaxed);
+    assert_eq!(id.load(Ordering::Relaxed).0, 2);
+}
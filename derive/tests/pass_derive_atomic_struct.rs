@@ -0,0 +1,37 @@
+use atomiq::prelude::*;
+
+#[derive(AtomicStruct, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    pub enabled: bool,
+}
+
+#[derive(AtomicStruct, Debug, PartialEq)]
+#[atomiq(name = ConsistentRange, consistent)]
+struct Range {
+    start: u32,
+    end: u32,
+}
+
+fn main() {
+    let stats = AtomicStats::from(Stats { hits: 1, ..Default::default() });
+
+    stats.fetch_add_hits(2, Ordering::Relaxed);
+    stats.fetch_add_misses(5, Ordering::Relaxed);
+    stats.fetch_sub_misses(1, Ordering::Relaxed);
+    stats.store_enabled(true, Ordering::Relaxed);
+
+    assert_eq!(stats.load_hits(Ordering::Relaxed), 3);
+    assert_eq!(stats.hits.load(Ordering::Relaxed), 3);
+    assert_eq!(stats.snapshot(Ordering::Relaxed), Stats { hits: 3, misses: 4, enabled: true });
+
+    stats.reset(Ordering::Relaxed);
+    assert_eq!(stats.snapshot(Ordering::Relaxed), Stats::default());
+
+    let range = ConsistentRange::new(Range { start: 0, end: 10 });
+
+    range.store_all(Range { start: 5, end: 15 }, Ordering::Relaxed);
+    assert_eq!(range.fetch_add_end(5, Ordering::Relaxed), 15);
+    assert_eq!(range.snapshot(Ordering::Acquire), Range { start: 5, end: 20 });
+}
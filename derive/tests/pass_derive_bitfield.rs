@@ -0,0 +1,69 @@
+use atomiq::prelude::*;
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable, Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+enum State {
+    Idle,
+    Running,
+    Stopped,
+}
+
+#[derive(Atomizable, Clone, Copy, Debug, PartialEq)]
+struct Version(u8, u8);
+
+#[derive(Atomizable, Debug, PartialEq)]
+#[atomiq(repr = u64)]
+struct Task {
+    #[atomiq(bits = 1)]
+    ready: bool,
+    #[atomiq(bits = 2)]
+    state: State,
+    #[atomiq(bits = 5)]
+    offset: i8,
+    version: Version,
+    id: u32,
+}
+
+fn main() {
+    let task = Task {
+        ready: true,
+        state: State::Stopped,
+        offset: -7,
+        version: Version(1, 2),
+        id: 0xdead_beef,
+    };
+
+    let atom: u64 = Task { ..task }.pack();
+    assert_eq!(atom & 0b111, 0b101);
+    assert_eq!(atom >> 8 & 0xffff, 0x0201);
+    assert_eq!(Task::unpack(atom), task);
+
+    let version: u16 = Version(3, 4).pack();
+    assert_eq!(version, 0x0403);
+
+    let atomic: Atomic<Task> = task.atomize();
+
+    assert_eq!(atomic.load_state(Ordering::Relaxed), State::Stopped);
+    atomic.store_state(State::Running, Ordering::Relaxed);
+    atomic.store_offset(15, Ordering::Relaxed);
+
+    let previous = atomic
+        .update_field_id(Ordering::Relaxed, Ordering::Relaxed, |id| Some(id + 1))
+        .unwrap();
+    assert_eq!(previous.id, 0xdead_beef);
+    assert!(atomic
+        .update_field_ready(Ordering::Relaxed, Ordering::Relaxed, |_| None)
+        .is_err());
+
+    assert_eq!(
+        atomic.load(Ordering::Relaxed),
+        Task {
+            ready: true,
+            state: State::Running,
+            offset: 15,
+            version: Version(1, 2),
+            id: 0xdead_bef0,
+        },
+    );
+}
@@ -0,0 +1,33 @@
+use atomiq::prelude::*;
+use atomiq_derive::{Atomizable, BitAtomizable, IntAtomizable};
+
+#[derive(Atomizable, Debug, PartialEq)]
+#[repr(u8)]
+enum Sparse {
+    A = 1,
+    B = 4,
+    C,
+}
+
+#[derive(Atomizable, BitAtomizable, IntAtomizable, Debug, PartialEq)]
+#[repr(u8)]
+#[atomiq(default = Unknown, allow_ops)]
+enum Level {
+    Low,
+    High,
+    Unknown,
+}
+
+fn main() {
+    assert_eq!(Sparse::unpack(4), Sparse::B);
+    assert_eq!(Sparse::unpack(5), Sparse::C);
+    assert!(std::panic::catch_unwind(|| Sparse::unpack(2)).is_err());
+
+    let level: Atomic<Level> = Level::High.atomize();
+    level.fetch_add(Level::High, Ordering::Relaxed);
+    assert_eq!(level.load(Ordering::Relaxed), Level::Unknown);
+    level.fetch_add(Level::High, Ordering::Relaxed);
+    assert_eq!(level.load(Ordering::Relaxed), Level::Unknown);
+    level.fetch_and(Level::High, Ordering::Relaxed);
+    assert_eq!(level.load(Ordering::Relaxed), Level::High);
+}
@@ -0,0 +1,26 @@
+use atomiq::prelude::*;
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable, Debug, PartialEq)]
+#[repr(align(4), u8)]
+enum Aligned {
+    A,
+    B,
+}
+
+#[derive(Atomizable, Debug, PartialEq)]
+#[repr(i16)]
+#[repr(align(2))]
+enum Split {
+    A = -1,
+    B,
+}
+
+fn main() {
+    let atom: u8 = Aligned::B.pack();
+    assert_eq!(Aligned::unpack(atom), Aligned::B);
+
+    let atom: i16 = Split::A.pack();
+    assert_eq!(atom, -1);
+    assert_eq!(Split::unpack(0), Split::B);
+}
@@ -0,0 +1,38 @@
+use atomiq::prelude::*;
+
+#[derive(AtomicFlags)]
+enum Permission {
+    Read,
+    Write,
+    ExecuteOnce,
+}
+
+fn main() {
+    let mut flags = PermissionFlags::READ | PermissionFlags::EXECUTE_ONCE;
+
+    assert!(flags.contains(PermissionFlags::READ));
+    assert!(!flags.contains(PermissionFlags::WRITE));
+    assert_eq!(flags.bits(), 0b101);
+
+    flags.toggle(PermissionFlags::READ | PermissionFlags::WRITE);
+    assert_eq!(flags, PermissionFlags::WRITE | PermissionFlags::EXECUTE_ONCE);
+    assert_eq!(!flags, PermissionFlags::READ);
+    assert_eq!(PermissionFlags::from(Permission::Write), PermissionFlags::WRITE);
+    assert_eq!(PermissionFlags::from_bits(0b1000), None);
+
+    let set: Vec<_> = flags.iter().collect();
+    assert_eq!(set, [PermissionFlags::WRITE, PermissionFlags::EXECUTE_ONCE]);
+    assert_eq!(format!("{:?}", flags), "PermissionFlags(WRITE | EXECUTE_ONCE)");
+    assert_eq!(format!("{:?}", PermissionFlags::empty()), "PermissionFlags(empty)");
+
+    let atomic: Atomic<PermissionFlags> = flags.atomize();
+
+    let previous = atomic.fetch_insert(PermissionFlags::READ, Ordering::Relaxed);
+    assert_eq!(previous, flags);
+    assert!(atomic.load(Ordering::Relaxed).is_all());
+
+    atomic.fetch_remove(PermissionFlags::WRITE, Ordering::Relaxed);
+    atomic.fetch_toggle(PermissionFlags::EXECUTE_ONCE, Ordering::Relaxed);
+    assert!(atomic.contains(PermissionFlags::READ, Ordering::Relaxed));
+    assert_eq!(atomic.load(Ordering::Relaxed), PermissionFlags::READ);
+}
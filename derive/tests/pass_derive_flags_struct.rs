@@ -0,0 +1,21 @@
+use atomiq::prelude::*;
+
+#[derive(AtomicFlags, Debug, PartialEq)]
+#[atomiq(name = Features, repr = u32)]
+struct FeatureSet {
+    logging: bool,
+    fast_path: bool,
+}
+
+fn main() {
+    let features = Features::from(FeatureSet { logging: false, fast_path: true });
+
+    assert_eq!(features, Features::FAST_PATH);
+    assert_eq!(features.bits(), 0b10u32);
+    assert_eq!(FeatureSet::from(Features::all()), FeatureSet { logging: true, fast_path: true });
+
+    let atomic: Atomic<Features> = Atomic::from(features);
+    atomic.fetch_insert(Features::LOGGING, Ordering::Relaxed);
+
+    assert_eq!(atomic.load(Ordering::Relaxed).iter().count(), 2);
+}
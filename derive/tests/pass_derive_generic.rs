@@ -0,0 +1,51 @@
+use std::marker::PhantomData;
+
+use atomiq::prelude::*;
+use atomiq_derive::{Atomizable, BitAtomizable, IntAtomizable};
+
+#[derive(Atomizable, BitAtomizable, IntAtomizable)]
+struct Id<T>(u32, PhantomData<T>);
+
+#[derive(Atomizable)]
+struct Wrapper<T>
+where
+    T: Copy,
+{
+    value: T,
+}
+
+#[derive(Atomizable)]
+struct Tagged<T> {
+    _marker: PhantomData<fn() -> T>,
+    index: usize,
+    #[atomiq(skip)]
+    _unit: (),
+}
+
+#[derive(Atomizable, Debug, PartialEq)]
+struct Packed {
+    #[atomiq(bits = 4)]
+    low: u8,
+    _marker: PhantomData<u8>,
+    #[atomiq(bits = 4)]
+    high: u8,
+}
+
+struct User;
+
+fn main() {
+    let id: Atomic<Id<User>> = Id(1, PhantomData).atomize();
+    id.fetch_add(Id(2, PhantomData), Ordering::Relaxed);
+    id.fetch_or(Id(4, PhantomData), Ordering::Relaxed);
+    assert_eq!(id.load(Ordering::Relaxed).0, 7);
+
+    let wrapper: Atomic<Wrapper<i16>> = Wrapper { value: -3 }.atomize();
+    assert_eq!(wrapper.swap(Wrapper { value: 5 }, Ordering::Relaxed).value, -3);
+
+    let tagged = Tagged::<User>::unpack(9);
+    assert_eq!(tagged.pack(), 9);
+
+    let atom: u8 = Packed { low: 1, _marker: PhantomData, high: 2 }.pack();
+    assert_eq!(atom, 0x21);
+    assert_eq!(Packed::unpack(atom), Packed { low: 1, _marker: PhantomData, high: 2 });
+}
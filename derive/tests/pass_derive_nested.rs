@@ -0,0 +1,33 @@
+use atomiq::prelude::*;
+use atomiq_derive::{Atomizable, BitAtomizable, IntAtomizable};
+
+#[derive(Atomizable, Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+enum State {
+    Idle,
+    Busy,
+}
+
+#[derive(Atomizable, Debug, PartialEq)]
+struct Phase(State);
+
+#[derive(Atomizable, BitAtomizable, IntAtomizable, Debug, PartialEq)]
+struct Count(u16);
+
+#[derive(Atomizable, BitAtomizable, IntAtomizable, Debug, PartialEq)]
+struct Total {
+    count: Count,
+}
+
+fn main() {
+    let atom: u8 = Phase(State::Busy).pack();
+    assert_eq!(atom, 1);
+
+    let phase: Atomic<Phase> = Phase(State::Idle).atomize();
+    phase.store(Phase(State::Busy), Ordering::Relaxed);
+    assert_eq!(phase.load(Ordering::Relaxed), Phase(State::Busy));
+
+    let total: Atomic<Total> = Total { count: Count(1) }.atomize();
+    total.fetch_add(Total { count: Count(2) }, Ordering::Relaxed);
+    assert_eq!(total.load(Ordering::Relaxed), Total { count: Count(3) });
+}
@@ -0,0 +1,44 @@
+use atomiq_derive::Atomizable;
+
+mod reexport {
+    pub use atomiq as sync;
+}
+
+#[derive(Atomizable)]
+#[atomiq(int, crate = "reexport::sync")]
+struct Counter(u32);
+
+#[derive(Atomizable, Debug, PartialEq)]
+#[atomiq(bit, crate = "reexport::sync")]
+struct Mask {
+    #[atomiq(bits = 4)]
+    low: u8,
+    #[atomiq(bits = 4)]
+    high: u8,
+}
+
+#[derive(Atomizable, Debug, PartialEq)]
+#[repr(u8)]
+#[atomiq(int, default = Overflow)]
+enum Level {
+    Low,
+    High,
+    Overflow,
+}
+
+fn main() {
+    use reexport::sync::{Atomic, Atomize, Ordering};
+
+    let counter: Atomic<Counter> = Counter(1).atomize();
+    counter.fetch_add(Counter(2), Ordering::Relaxed);
+    counter.fetch_or(Counter(4), Ordering::Relaxed);
+    assert_eq!(counter.load(Ordering::Relaxed).0, 7);
+
+    let mask: Atomic<Mask> = Mask { low: 0xf, high: 0 }.atomize();
+    mask.fetch_xor(Mask { low: 0xf, high: 0xf }, Ordering::Relaxed);
+    assert_eq!(mask.load(Ordering::Relaxed), Mask { low: 0, high: 0xf });
+
+    let level: Atomic<Level> = Level::High.atomize();
+    level.fetch_add(Level::High, Ordering::Relaxed);
+    assert_eq!(level.load(Ordering::Relaxed), Level::Overflow);
+}
@@ -0,0 +1,56 @@
+use atomiq::prelude::*;
+use atomiq_derive::Atomizable;
+
+#[derive(Atomizable, Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+enum Role {
+    Client,
+    Server,
+}
+
+#[derive(Atomizable, Clone, Copy, Debug, PartialEq)]
+enum Conn {
+    Idle,
+    Connecting(u16),
+    Open { fd: u32, role: Role },
+}
+
+#[derive(Atomizable, Debug, PartialEq)]
+#[atomiq(repr = u16, default = Unknown)]
+enum Small {
+    Unknown,
+    Offset(#[atomiq(bits = 4)] i8),
+    Flag { set: bool },
+}
+
+fn main() {
+    // 2 tag bits, then a 32-bit descriptor and an 8-bit role.
+    let atom: u64 = Conn::Open { fd: 3, role: Role::Server }.pack();
+    assert_eq!(atom, 2 | 3 << 2 | 1 << 34);
+    assert_eq!(Conn::unpack(atom), Conn::Open { fd: 3, role: Role::Server });
+
+    let conn: Atomic<Conn> = Conn::Idle.atomize();
+    assert_eq!(
+        conn.compare_exchange(Conn::Idle, Conn::Connecting(80), Ordering::AcqRel, Ordering::Acquire),
+        Ok(Conn::Idle),
+    );
+    assert_eq!(
+        conn.compare_exchange(Conn::Idle, Conn::Connecting(443), Ordering::AcqRel, Ordering::Acquire),
+        Err(Conn::Connecting(80)),
+    );
+    assert_eq!(
+        conn.compare_exchange(
+            Conn::Connecting(80),
+            Conn::Open { fd: 7, role: Role::Client },
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ),
+        Ok(Conn::Connecting(80)),
+    );
+    assert_eq!(conn.load(Ordering::Acquire), Conn::Open { fd: 7, role: Role::Client });
+
+    let atom: u16 = Small::Offset(-2).pack();
+    assert_eq!(Small::unpack(atom), Small::Offset(-2));
+    assert_eq!(Small::unpack(Small::Flag { set: true }.pack()), Small::Flag { set: true });
+    assert_eq!(Small::unpack(3), Small::Unknown);
+}
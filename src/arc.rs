@@ -0,0 +1,410 @@
+//! Atomically swappable `Arc`s.
+//!
+//! See [`AtomicArc`] and [`AtomicOptionArc`] for more information.
+
+use core::fmt::{self, Debug, Formatter};
+use core::marker::PhantomData;
+use core::ptr;
+use crate::atom::spin_loop;
+use crate::prelude::*;
+
+/// An atomic `Option<Arc<T>>`.
+///
+/// This is a lock-free version of `RwLock<Option<Arc<T>>>`, useful for values that are read
+/// often and replaced rarely, such as hot-reloadable configuration.
+///
+/// Loads never block. Operations that replace the value wait for the loads that are in flight
+/// at that moment to take their own reference before the replaced `Arc` is handed back, so the
+/// old value is never freed while a reader is still about to use it. Such a load is only a
+/// handful of instructions long, but under extreme read pressure writers may spin for a while.
+///
+/// All operations use [`Ordering::SeqCst`] internally.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::arc::AtomicOptionArc;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let option: AtomicOptionArc<i32> = AtomicOptionArc::none();
+///
+/// assert!(option.load().is_none());
+///
+/// option.store(Some(Arc::new(42)));
+///
+/// assert_eq!(option.load().as_deref(), Some(&42));
+/// # });
+/// ```
+pub struct AtomicOptionArc<T> {
+    ptr: Atomic<*mut T>,
+    readers: Atomic<usize>,
+    _marker: PhantomData<Option<Arc<T>>>,
+}
+
+impl<T> AtomicOptionArc<T> {
+    /// Creates a new atomic option with the given value.
+    pub fn new(value: Option<Arc<T>>) -> Self {
+        Self {
+            ptr: Atomic::from(Self::into_ptr(value)),
+            readers: Atomic::from(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a new atomic option with no value.
+    pub fn none() -> Self {
+        Self::new(None)
+    }
+
+    /// Creates a new atomic option with a value.
+    pub fn some(value: Arc<T>) -> Self {
+        Self::new(Some(value))
+    }
+
+    fn into_ptr(value: Option<Arc<T>>) -> *mut T {
+        value.map_or(ptr::null_mut(), |arc| Arc::into_raw(arc) as *mut T)
+    }
+
+    fn as_ptr(value: Option<&Arc<T>>) -> *mut T {
+        value.map_or(ptr::null_mut(), |arc| Arc::as_ptr(arc) as *mut T)
+    }
+
+    /// Takes back the reference owned by a pointer obtained from [`Self::into_ptr`].
+    ///
+    /// # Safety
+    /// The pointer must be null or own one strong reference, which is consumed.
+    unsafe fn from_ptr(ptr: *mut T) -> Option<Arc<T>> {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Arc::from_raw(ptr))
+        }
+    }
+
+    /// Creates a new reference from a pointer obtained from [`Self::into_ptr`].
+    ///
+    /// # Safety
+    /// The pointer must be null or point to an `Arc` that stays alive during the call.
+    unsafe fn clone_ptr(ptr: *mut T) -> Option<Arc<T>> {
+        if ptr.is_null() {
+            None
+        } else {
+            Arc::increment_strong_count(ptr);
+            Some(Arc::from_raw(ptr))
+        }
+    }
+
+    /// Waits until every load that might have seen a replaced pointer has taken its reference.
+    fn wait_for_readers(&self) {
+        // A read-modify-write always observes the latest count, so a reader that saw the
+        // replaced pointer cannot be missed.
+        while self.readers.fetch_add(0, Ordering::SeqCst) != 0 {
+            spin_loop();
+        }
+    }
+
+    /// Loads the value, returning a new reference to it.
+    pub fn load(&self) -> Option<Arc<T>> {
+        self.readers.fetch_add(1, Ordering::SeqCst);
+        // The pointer cannot be released while `readers` is non-zero.
+        let value = unsafe { Self::clone_ptr(self.ptr.load(Ordering::SeqCst)) };
+        self.readers.fetch_sub(1, Ordering::SeqCst);
+        value
+    }
+
+    /// Returns whether the option is `Some`.
+    pub fn is_some(&self) -> bool {
+        !self.ptr.load(Ordering::SeqCst).is_null()
+    }
+
+    /// Returns whether the option is `None`.
+    pub fn is_none(&self) -> bool {
+        !self.is_some()
+    }
+
+    /// Stores a value, dropping the previous one.
+    pub fn store(&self, value: Option<Arc<T>>) {
+        self.swap(value);
+    }
+
+    /// Stores a value, returning the previous one.
+    pub fn swap(&self, value: Option<Arc<T>>) -> Option<Arc<T>> {
+        let previous = self.ptr.swap(Self::into_ptr(value), Ordering::SeqCst);
+        self.wait_for_readers();
+        // The reference owned by the atomic is now ours.
+        unsafe { Self::from_ptr(previous) }
+    }
+
+    /// Takes the value out, leaving `None` in its place.
+    pub fn take(&self) -> Option<Arc<T>> {
+        self.swap(None)
+    }
+
+    /// Stores a value if the current value points to the same allocation as `current`.
+    ///
+    /// Values are compared by pointer identity, not by [`PartialEq`]. The return value indicates
+    /// whether the store was successful and contains the previous value. On failure, `new` is
+    /// dropped.
+    pub fn compare_and_swap(
+        &self,
+        current: Option<&Arc<T>>,
+        new: Option<Arc<T>>,
+    ) -> Result<Option<Arc<T>>, Option<Arc<T>>> {
+        let new = Self::into_ptr(new);
+
+        self.readers.fetch_add(1, Ordering::SeqCst);
+        match self.ptr.compare_exchange(Self::as_ptr(current), new, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(previous) => {
+                self.readers.fetch_sub(1, Ordering::SeqCst);
+                self.wait_for_readers();
+                Ok(unsafe { Self::from_ptr(previous) })
+            }
+            Err(actual) => {
+                let actual = unsafe { Self::clone_ptr(actual) };
+                self.readers.fetch_sub(1, Ordering::SeqCst);
+                drop(unsafe { Self::from_ptr(new) });
+                Err(actual)
+            }
+        }
+    }
+
+    /// Replaces the value with the result of `f`, retrying if another thread changed the value
+    /// in the meantime (read-copy-update).
+    ///
+    /// `f` may be called multiple times. Returns the replaced value.
+    pub fn rcu<F>(&self, mut f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(Option<&Arc<T>>) -> Option<Arc<T>>,
+    {
+        let mut current = self.load();
+        loop {
+            let new = f(current.as_ref());
+            match self.compare_and_swap(current.as_ref(), new) {
+                Ok(previous) => return previous,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Consumes the atomic option, returning the contained value.
+    pub fn into_inner(self) -> Option<Arc<T>> {
+        self.take()
+    }
+}
+
+impl<T> Drop for AtomicOptionArc<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Self::from_ptr(self.ptr.load(Ordering::Acquire)) });
+    }
+}
+
+impl<T> Default for AtomicOptionArc<T> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<T> From<Option<Arc<T>>> for AtomicOptionArc<T> {
+    fn from(value: Option<Arc<T>>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> From<Arc<T>> for AtomicOptionArc<T> {
+    fn from(value: Arc<T>) -> Self {
+        Self::some(value)
+    }
+}
+
+impl<T: Debug> Debug for AtomicOptionArc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicOptionArc").field(&self.load()).finish()
+    }
+}
+
+/// An atomic `Arc<T>`.
+///
+/// Like [`AtomicOptionArc`], but always holds a value.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::arc::AtomicArc;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let config: AtomicArc<u32> = AtomicArc::from(Arc::new(1));
+///
+/// let previous = config.rcu(|old| **old + 1);
+///
+/// assert_eq!(*previous, 1);
+/// assert_eq!(*config.load(), 2);
+/// # });
+/// ```
+pub struct AtomicArc<T>(AtomicOptionArc<T>);
+
+fn expect_some<T>(value: Option<T>) -> T {
+    value.expect("AtomicArc is never empty")
+}
+
+impl<T> AtomicArc<T> {
+    /// Creates a new atomic `Arc` with the given value.
+    pub fn new(value: Arc<T>) -> Self {
+        Self(AtomicOptionArc::some(value))
+    }
+
+    /// Loads the value, returning a new reference to it.
+    pub fn load(&self) -> Arc<T> {
+        expect_some(self.0.load())
+    }
+
+    /// Stores a value, dropping the previous one.
+    pub fn store(&self, value: Arc<T>) {
+        self.0.store(Some(value));
+    }
+
+    /// Stores a value, returning the previous one.
+    pub fn swap(&self, value: Arc<T>) -> Arc<T> {
+        expect_some(self.0.swap(Some(value)))
+    }
+
+    /// Stores a value if the current value points to the same allocation as `current`.
+    ///
+    /// Values are compared by pointer identity, not by [`PartialEq`]. The return value indicates
+    /// whether the store was successful and contains the previous value. On failure, `new` is
+    /// dropped.
+    pub fn compare_and_swap(&self, current: &Arc<T>, new: Arc<T>) -> Result<Arc<T>, Arc<T>> {
+        self.0.compare_and_swap(Some(current), Some(new))
+            .map(expect_some)
+            .map_err(expect_some)
+    }
+
+    /// Replaces the value with the result of `f`, retrying if another thread changed the value
+    /// in the meantime (read-copy-update).
+    ///
+    /// `f` may be called multiple times. Returns the replaced value.
+    pub fn rcu<F, R>(&self, mut f: F) -> Arc<T>
+    where
+        F: FnMut(&Arc<T>) -> R,
+        R: Into<Arc<T>>,
+    {
+        expect_some(self.0.rcu(|current| Some(f(expect_some(current)).into())))
+    }
+
+    /// Consumes the atomic `Arc`, returning the contained value.
+    pub fn into_inner(self) -> Arc<T> {
+        expect_some(self.0.into_inner())
+    }
+}
+
+impl<T: Default> Default for AtomicArc<T> {
+    fn default() -> Self {
+        Self::new(Arc::default())
+    }
+}
+
+impl<T> From<Arc<T>> for AtomicArc<T> {
+    fn from(value: Arc<T>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Debug> Debug for AtomicArc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicArc").field(&self.load()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+    #[cfg(feature = "loom")]
+    use loom::thread;
+
+    #[test]
+    fn test_atomic_option_arc_sync() {
+        try_init_model(|| {
+            let option: AtomicOptionArc<i32> = AtomicOptionArc::none();
+            assert!(option.is_none());
+            assert!(option.load().is_none());
+
+            let first = Arc::new(1);
+            assert!(option.swap(Some(first.clone())).is_none());
+            assert!(option.is_some());
+            assert!(Arc::ptr_eq(&option.load().unwrap(), &first));
+
+            // Equal values in different allocations do not match.
+            let other = Arc::new(1);
+            let actual = option.compare_and_swap(Some(&other), None).unwrap_err();
+            assert!(Arc::ptr_eq(&actual.unwrap(), &first));
+
+            let previous = option.compare_and_swap(Some(&first), Some(Arc::new(2))).unwrap();
+            assert!(Arc::ptr_eq(&previous.unwrap(), &first));
+            assert_eq!(Arc::strong_count(&first), 1);
+
+            assert_eq!(option.take().as_deref(), Some(&2));
+            assert!(option.is_none());
+        });
+    }
+
+    #[test]
+    fn test_atomic_arc_rcu() {
+        try_init_model(|| {
+            let arc: AtomicArc<i32> = AtomicArc::from(Arc::new(1));
+
+            let previous = arc.rcu(|old| **old * 10);
+            assert_eq!(*previous, 1);
+            assert_eq!(*arc.load(), 10);
+
+            let value = arc.load();
+            assert_eq!(Arc::strong_count(&value), 2);
+            drop(arc);
+            assert_eq!(Arc::strong_count(&value), 1);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_atomic_arc_loom_load_swap() {
+        try_init_model(|| {
+            let arc: Arc<AtomicArc<i32>> = Arc::new(AtomicArc::new(Arc::new(1)));
+
+            let reader = thread::spawn({
+                let arc = arc.clone();
+                move || {
+                    let value = *arc.load();
+                    assert!(value == 1 || value == 2);
+                }
+            });
+
+            // The replaced value is dropped here while the reader may still be loading it.
+            arc.store(Arc::new(2));
+
+            reader.join().unwrap();
+            assert_eq!(*arc.load(), 2);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_atomic_arc_loom_rcu() {
+        try_init_model(|| {
+            let arc: Arc<AtomicArc<i32>> = Arc::new(AtomicArc::new(Arc::new(0)));
+
+            let threads: [_; 2] = core::array::from_fn(|_| thread::spawn({
+                let arc = arc.clone();
+                move || {
+                    arc.rcu(|old| **old + 1);
+                }
+            }));
+
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(*arc.load(), 2);
+        });
+    }
+}
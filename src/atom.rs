@@ -6,15 +6,137 @@ cfg_if!(
     if #[cfg(feature = "loom")] {
         use loom::sync::atomic as a;
         pub use loom::sync::Arc;
+        pub(crate) use loom::hint::spin_loop;
+    } else if #[cfg(feature = "shuttle")] {
+        use shuttle::sync::atomic as a;
+        pub use shuttle::sync::Arc;
+        pub(crate) use shuttle::hint::spin_loop;
     } else {
         use core::sync::atomic as a;
         #[cfg(feature = "alloc")]
         pub use alloc::sync::Arc;
+        pub(crate) use core::hint::spin_loop;
     }
 );
-pub use a::fence;
+
+cfg_if!(
+    if #[cfg(all(feature = "race-check", not(any(feature = "loom", feature = "shuttle"))))] {
+        use crate::race_check::{raced_impl, Raced};
+        pub use crate::race_check::fence;
+
+        /// The provider of an atom, wrapped to track the clocks released through it.
+        macro_rules! raced {
+            ($provider:ty) => { Raced<$provider> };
+        }
+    } else {
+        pub use a::fence;
+
+        macro_rules! raced {
+            ($provider:ty) => { $provider };
+        }
+
+        macro_rules! raced_impl {
+            ($($tokens:tt)*) => {};
+        }
+    }
+);
+
+cfg_if!(
+    if #[cfg(feature = "trace")] {
+        use crate::trace::{Operation, Traced};
+
+        /// The provider of an atom, wrapped to record its operations.
+        macro_rules! traced {
+            ($atom:ty, $provider:ty) => { Traced<$atom, $provider> };
+        }
+
+        /// The provider wrapped by the recording one.
+        macro_rules! untraced {
+            ($provider:expr) => { $provider.inner() };
+        }
+
+        /// Records an operation on the recording provider.
+        macro_rules! trace {
+            ($provider:expr, $operation:ident, $ordering:expr, $old:expr, $new:expr) => {
+                $provider.record(Operation::$operation, $ordering, $old, $new)
+            };
+            ($provider:expr, $operation:ident, $success:expr, $failure:expr, $result:expr, $new:expr) => {
+                $provider.record_exchange(Operation::$operation, $success, $failure, $result, $new)
+            };
+        }
+    } else {
+        macro_rules! traced {
+            ($atom:ty, $provider:ty) => { $provider };
+        }
+
+        macro_rules! untraced {
+            ($provider:expr) => { $provider };
+        }
+
+        macro_rules! trace {
+            ($($tokens:tt)*) => {};
+        }
+    }
+);
+
+cfg_if!(
+    if #[cfg(feature = "stats")] {
+        use crate::stats::{Counted, Kind};
+
+        /// The provider of an atom, wrapped to count its operations.
+        macro_rules! counted {
+            ($atom:ty, $provider:ty) => { Counted<$atom, $provider> };
+        }
+
+        /// The provider wrapped by the counting one.
+        macro_rules! uncounted {
+            ($provider:expr) => { $provider.inner() };
+        }
+
+        /// Counts operations of a kind on the provider, once by default.
+        macro_rules! count {
+            ($provider:expr, $kind:ident) => {
+                $provider.count(Kind::$kind, 1)
+            };
+            ($provider:expr, $kind:ident, $count:expr) => {
+                $provider.count(Kind::$kind, $count)
+            };
+        }
+    } else {
+        macro_rules! counted {
+            ($atom:ty, $provider:ty) => { $provider };
+        }
+
+        macro_rules! uncounted {
+            ($provider:expr) => { $provider };
+        }
+
+        macro_rules! count {
+            ($($tokens:tt)*) => {};
+        }
+    }
+);
+
+/// The provider of an atom, wrapped by the enabled instrumentation.
+macro_rules! provider {
+    ($atom:ty, $provider:ty) => { counted!($atom, traced!($atom, raced!($provider))) };
+}
+
+/// The underlying provider.
+macro_rules! inner {
+    ($provider:expr) => { untraced!(uncounted!($provider)) };
+}
+
+/// Records an operation on the provider.
+macro_rules! record {
+    ($provider:expr, $($args:tt)*) => { trace!(uncounted!($provider), $($args)*) };
+}
 
 /// A primitive atomizable value.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a primitive atom",
+    note = "derive or implement `Atomizable` to pack the type into a primitive atom"
+)]
 pub trait Atom: Sized + Clone + Copy + Debug {
     /// The provider of the atomic operations.
     type Provider: From<Self> + Debug + Default;
@@ -50,9 +172,30 @@ pub trait Atom: Sized + Clone + Copy + Debug {
     ) -> Result<Self, Self>
     where
         F: FnMut(Self) -> Option<Self>;
+    /// Names the provider in trace events, formatting its values with `format`.
+    ///
+    /// Does nothing by default, for atoms whose provider does not record its operations.
+    #[cfg(feature = "trace")]
+    #[doc(hidden)]
+    fn set_trace_name(provider: &Self::Provider, name: &'static str, format: fn(Self) -> std::string::String) {
+        let _ = (provider, name, format);
+    }
+    /// Returns the operation counters of the provider, if it counts its operations.
+    ///
+    /// Returns `None` by default.
+    #[cfg(feature = "stats")]
+    #[doc(hidden)]
+    fn stats_counters(provider: &Self::Provider) -> Option<&std::sync::Arc<crate::stats::Counters>> {
+        let _ = provider;
+        None
+    }
 }
 
 /// A primitive atomizable bit value.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not support atomic bitwise operations",
+    note = "`BitAtomizable` requires the `Atom` of the type to be `bool` or an integer"
+)]
 pub trait BitAtom: Atom {
     #[doc(hidden)]
     fn fetch_and(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self;
@@ -68,6 +211,10 @@ pub trait BitAtom: Atom {
 }
 
 /// A primitive atomizable integer value.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not support atomic integer operations",
+    note = "`IntAtomizable` requires the `Atom` of the type to be an integer"
+)]
 pub trait IntAtom: Atom {
     #[doc(hidden)]
     fn fetch_add(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self;
@@ -89,36 +236,58 @@ macro_rules! or_def {
 }
 
 macro_rules! atom_impl {
-    ($atom:ty => $provider:ident $length:literal) => {
+    (@atom $atom:ty => $provider:ty, $length:literal $(, <$generic:ident>)?) => {
         #[cfg(target_has_atomic = $length)]
-        use a::$provider;
+        raced_impl!($atom, $provider $(, <$generic>)?);
 
         #[cfg(target_has_atomic = $length)]
-        impl Atom for $atom {
-            type Provider = $provider;
+        impl$(<$generic>)? Atom for $atom {
+            type Provider = provider!($atom, $provider);
 
-            fn load(provider: &$provider, ordering: Ordering) -> Self {
-                provider.load(ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn load(provider: &Self::Provider, ordering: Ordering) -> Self {
+                let value = inner!(provider).load(ordering);
+                record!(provider, Load, ordering, Some(value), None);
+                count!(provider, Load);
+                value
             }
 
-            fn store(provider: &$provider, value: Self, ordering: Ordering) {
-                provider.store(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn store(provider: &Self::Provider, value: Self, ordering: Ordering) {
+                inner!(provider).store(value, ordering);
+                record!(provider, Store, ordering, None, Some(value));
+                count!(provider, Store);
             }
 
-            fn swap(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.swap(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn swap(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).swap(value, ordering);
+                record!(provider, Swap, ordering, Some(old), Some(value));
+                count!(provider, Swap);
+                old
             }
 
-            fn compare_exchange(provider: &$provider, current: Self, new: Self, success: Ordering, failure: Ordering) -> Result<Self, Self> {
-                provider.compare_exchange(current, new, success, failure)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn compare_exchange(provider: &Self::Provider, current: Self, new: Self, success: Ordering, failure: Ordering) -> Result<Self, Self> {
+                let result = inner!(provider).compare_exchange(current, new, success, failure);
+                record!(provider, CompareExchange, success, failure, result, Some(new));
+                count!(provider, CompareExchange);
+                count!(provider, CompareExchangeFailure, result.is_err() as u64);
+                result
             }
 
-            fn compare_exchange_weak(provider: &$provider, current: Self, new: Self, success: Ordering, failure: Ordering) -> Result<Self, Self> {
-                provider.compare_exchange_weak(current, new, success, failure)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn compare_exchange_weak(provider: &Self::Provider, current: Self, new: Self, success: Ordering, failure: Ordering) -> Result<Self, Self> {
+                let result = inner!(provider).compare_exchange_weak(current, new, success, failure);
+                record!(provider, CompareExchangeWeak, success, failure, result, Some(new));
+                count!(provider, CompareExchange);
+                count!(provider, CompareExchangeFailure, result.is_err() as u64);
+                result
             }
 
+            #[cfg_attr(feature = "race-check", track_caller)]
             fn fetch_update<F>(
-                provider: &$provider,
+                provider: &Self::Provider,
                 set_ordering: Ordering,
                 fetch_ordering: Ordering,
                 mut f: F,
@@ -126,51 +295,115 @@ macro_rules! atom_impl {
             where
                 F: FnMut(Self) -> Option<Self>
             {
-                provider.fetch_update(set_ordering, fetch_ordering, |value| f(value))
+                let mut new = None;
+                let mut attempts = 0;
+                let result = inner!(provider).fetch_update(set_ordering, fetch_ordering, |value| {
+                    attempts += 1;
+                    new = f(value);
+                    new
+                });
+                record!(provider, FetchUpdate, set_ordering, fetch_ordering, result, new);
+                count!(provider, FetchUpdate);
+                count!(provider, FetchUpdateRetry, attempts - 1);
+                result
+            }
+
+            #[cfg(feature = "trace")]
+            fn set_trace_name(provider: &Self::Provider, name: &'static str, format: fn(Self) -> std::string::String) {
+                uncounted!(provider).set_name(name, format);
+            }
+
+            #[cfg(feature = "stats")]
+            fn stats_counters(provider: &Self::Provider) -> Option<&std::sync::Arc<crate::stats::Counters>> {
+                Some(provider.counters())
             }
         }
     };
+    ($atom:ty => $provider:ident $length:literal) => {
+        #[cfg(target_has_atomic = $length)]
+        use a::$provider;
+
+        atom_impl!(@atom $atom => $provider, $length);
+    };
     ($atom:ty => $provider:ident $length:literal bit) => {
         atom_impl!($atom => $provider $length);
 
+        #[cfg(target_has_atomic = $length)]
+        raced_impl!($atom, $provider, bit);
+
         #[cfg(target_has_atomic = $length)]
         impl BitAtom for $atom {
-            fn fetch_and(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.fetch_and(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn fetch_and(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).fetch_and(value, ordering);
+                record!(provider, FetchAnd, ordering, Some(old), Some(old & value));
+                count!(provider, Bitwise);
+                old
             }
 
-            fn fetch_nand(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.fetch_nand(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn fetch_nand(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).fetch_nand(value, ordering);
+                record!(provider, FetchNand, ordering, Some(old), Some(!(old & value)));
+                count!(provider, Bitwise);
+                old
             }
 
-            fn fetch_or(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.fetch_or(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn fetch_or(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).fetch_or(value, ordering);
+                record!(provider, FetchOr, ordering, Some(old), Some(old | value));
+                count!(provider, Bitwise);
+                old
             }
 
-            fn fetch_xor(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.fetch_xor(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn fetch_xor(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).fetch_xor(value, ordering);
+                record!(provider, FetchXor, ordering, Some(old), Some(old ^ value));
+                count!(provider, Bitwise);
+                old
             }
         }
     };
     ($atom:ty => $provider:ident $length:literal int) => {
         atom_impl!($atom => $provider $length bit);
 
+        #[cfg(target_has_atomic = $length)]
+        raced_impl!($atom, $provider, int);
+
         #[cfg(target_has_atomic = $length)]
         impl IntAtom for $atom {
-            fn fetch_add(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.fetch_add(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn fetch_add(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).fetch_add(value, ordering);
+                record!(provider, FetchAdd, ordering, Some(old), Some(old.wrapping_add(value)));
+                count!(provider, Arithmetic);
+                old
             }
 
-            fn fetch_sub(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.fetch_sub(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn fetch_sub(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).fetch_sub(value, ordering);
+                record!(provider, FetchSub, ordering, Some(old), Some(old.wrapping_sub(value)));
+                count!(provider, Arithmetic);
+                old
             }
 
-            fn fetch_min(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.fetch_min(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn fetch_min(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).fetch_min(value, ordering);
+                record!(provider, FetchMin, ordering, Some(old), Some(old.min(value)));
+                count!(provider, Arithmetic);
+                old
             }
 
-            fn fetch_max(provider: &$provider, value: Self, ordering: Ordering) -> Self {
-                provider.fetch_max(value, ordering)
+            #[cfg_attr(feature = "race-check", track_caller)]
+            fn fetch_max(provider: &Self::Provider, value: Self, ordering: Ordering) -> Self {
+                let old = inner!(provider).fetch_max(value, ordering);
+                record!(provider, FetchMax, ordering, Some(old), Some(old.max(value)));
+                count!(provider, Arithmetic);
+                old
             }
         }
     };
@@ -198,4 +431,6 @@ atom_impls!(
     i64 => AtomicI64 "64" int;
     // i128 => AtomicI128 "128" int;
     isize => AtomicIsize "ptr" int;
-);
\ No newline at end of file
+);
+
+atom_impl!(@atom *mut T => a::AtomicPtr<T>, "ptr", <T>);
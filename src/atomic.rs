@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use crate::strict;
 
 /// An atomic value.
 #[derive(Debug)]
@@ -18,16 +19,19 @@ impl<T: Atomizable> From<T> for Atomic<T> {
 
 impl<T: Atomizable> Atomic<T> {
     /// Loads a value with the given ordering.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn load(&self, ordering: Ordering) -> T {
         T::unpack(Atom::load(&self.0, ordering))
     }
     
     /// Stores a value with the given ordering.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn store(&self, value: T, ordering: Ordering) {
         Atom::store(&self.0, value.pack(), ordering)
     }
     
     /// Swaps a value with the given ordering.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn swap(&self, value: T, ordering: Ordering) -> T {
         T::unpack(Atom::swap(&self.0, value.pack(), ordering))
     }
@@ -36,7 +40,9 @@ impl<T: Atomizable> Atomic<T> {
     /// 
     /// The return value indicates whether the store was successful and contains
     /// the previous value.
+    #[cfg_attr(any(feature = "race-check", feature = "strict"), track_caller)]
     pub fn compare_exchange(&self, current: T, new: T, success: Ordering, failure: Ordering) -> Result<T, T> {
+        strict::check_exchange("Atomic::compare_exchange", success, failure);
         Atom::compare_exchange(&self.0, current.pack(), new.pack(), success, failure)
             .map(|value| T::unpack(value))
             .map_err(|value| T::unpack(value))
@@ -49,7 +55,9 @@ impl<T: Atomizable> Atomic<T> {
     /// 
     /// This weak variant might fail even when the value is equal, but it may be
     /// more efficient on some platforms.
+    #[cfg_attr(any(feature = "race-check", feature = "strict"), track_caller)]
     pub fn compare_exchange_weak(&self, current: T, new: T, success: Ordering, failure: Ordering) -> Result<T, T> {
+        strict::check_exchange("Atomic::compare_exchange_weak", success, failure);
         Atom::compare_exchange_weak(&self.0, current.pack(), new.pack(), success, failure)
             .map(|value| T::unpack(value))
             .map_err(|value| T::unpack(value))
@@ -64,10 +72,12 @@ impl<T: Atomizable> Atomic<T> {
     /// 
     /// This method is _not_ provided by the hardware, but implemented by [compare_exchange_weak]
     /// and suffers some drawbacks.
+    #[cfg_attr(any(feature = "race-check", feature = "strict"), track_caller)]
     pub fn fetch_update<F>(&self, set_ordering: Ordering, get_ordering: Ordering, mut f: F) -> Result<T, T>
     where
         F: FnMut(T) -> Option<T>,
     {
+        strict::check_exchange("Atomic::fetch_update", set_ordering, get_ordering);
         Atom::fetch_update(&self.0, set_ordering, get_ordering, |value| f(T::unpack(value)).map(T::pack))
             .map(|value| T::unpack(value))
             .map_err(|value| T::unpack(value))
@@ -76,21 +86,25 @@ impl<T: Atomizable> Atomic<T> {
 
 impl<T: BitAtomizable> Atomic<T> {
     /// Fetches the value, applies a bitwise AND operation to it, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn fetch_and(&self, value: T, ordering: Ordering) -> T {
         T::unpack(T::Atom::fetch_and(&self.0, value.pack(), ordering))
     }
     
     /// Fetches the value, applies a bitwise NAND operation to it, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn fetch_nand(&self, value: T, ordering: Ordering) -> T {
         T::unpack(T::Atom::fetch_nand(&self.0, value.pack(), ordering))
     }
     
     /// Fetches the value, applies a bitwise OR operation to it, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn fetch_or(&self, value: T, ordering: Ordering) -> T {
         T::unpack(T::Atom::fetch_or(&self.0, value.pack(), ordering))
     }
     
     /// Fetches the value, applies a bitwise XOR operation to it, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn fetch_xor(&self, value: T, ordering: Ordering) -> T {
         T::unpack(T::Atom::fetch_xor(&self.0, value.pack(), ordering))
     }
@@ -98,26 +112,44 @@ impl<T: BitAtomizable> Atomic<T> {
 
 impl<T: IntAtomizable> Atomic<T> {
     /// Fetches the value, adds another value to it, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn fetch_add(&self, value: T, ordering: Ordering) -> T {
         T::unpack(T::Atom::fetch_add(&self.0, value.pack(), ordering))
     }
 
     /// Fetches the value, subtracts another value from it, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn fetch_sub(&self, value: T, ordering: Ordering) -> T {
         T::unpack(T::Atom::fetch_sub(&self.0, value.pack(), ordering))
     }
 
     /// Fetches the value, calculates the minimum with another value, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn fetch_min(&self, value: T, ordering: Ordering) -> T {
         T::unpack(T::Atom::fetch_min(&self.0, value.pack(), ordering))
     }
 
     /// Fetches the value, calculates the maximum with another value, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
     pub fn fetch_max(&self, value: T, ordering: Ordering) -> T {
         T::unpack(T::Atom::fetch_max(&self.0, value.pack(), ordering))
     }
 }
 
+impl<T: AffineAtomizable> Atomic<T> {
+    /// Fetches the value, adds a delta to it, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
+    pub fn fetch_add_delta(&self, delta: T::Delta, ordering: Ordering) -> T {
+        T::unpack(T::Atom::fetch_add(&self.0, T::pack_delta(delta), ordering))
+    }
+
+    /// Fetches the value, subtracts a delta from it, and stores the result.
+    #[cfg_attr(feature = "race-check", track_caller)]
+    pub fn fetch_sub_delta(&self, delta: T::Delta, ordering: Ordering) -> T {
+        T::unpack(T::Atom::fetch_sub(&self.0, T::pack_delta(delta), ordering))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,4 +171,15 @@ mod tests {
             assert_eq!(atomic.load(Ordering::Relaxed), 2);
         });
     }
+
+    #[test]
+    fn fetch_delta_test() {
+        try_init_model(|| {
+            let atomic = Atomic::from(10u32);
+
+            assert_eq!(atomic.fetch_add_delta(5, Ordering::Relaxed), 10);
+            assert_eq!(atomic.fetch_sub_delta(3, Ordering::Relaxed), 15);
+            assert_eq!(atomic.load(Ordering::Relaxed), 12);
+        });
+    }
 }
\ No newline at end of file
@@ -35,6 +35,27 @@ pub trait IntAtomizable: Atomizable<Atom: IntAtom> {}
 
 impl<T: IntAtom> IntAtomizable for T {}
 
+/// Trait for types that may be offset by a separate delta type, such as a timestamp by a
+/// duration or an index by a count.
+///
+/// The delta is packed into the same atom as the type, so [`Atomic::fetch_add_delta`] and
+/// [`Atomic::fetch_sub_delta`] use the native read-modify-write instructions.
+pub trait AffineAtomizable: Atomizable<Atom: IntAtom> {
+    /// The type of the offsets.
+    type Delta;
+
+    /// Packs the delta into the primitive representation of the type.
+    fn pack_delta(delta: Self::Delta) -> Self::Atom;
+}
+
+impl<T: IntAtom> AffineAtomizable for T {
+    type Delta = T;
+
+    fn pack_delta(delta: Self::Delta) -> Self::Atom {
+        delta
+    }
+}
+
 /// Extension trait for converting values into atomic.
 /// 
 /// This trait is implemented for all types that implement `Atomizable`.
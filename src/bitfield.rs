@@ -0,0 +1,187 @@
+//! Packing of several values into a single atom.
+//!
+//! Structs with more than one field can derive `Atomizable`, packing each field into a range of
+//! bits of one integer atom. The width of a field is set with `#[atomiq(bits = N)]` and defaults
+//! to the full width of the field's atom. The integer type of the struct is set with
+//! `#[atomiq(repr = u64)]` and defaults to the smallest that fits, or `u64` if the widths are not
+//! known to the derive.
+//!
+//! Fields are packed in declaration order, starting from the least significant bit. Values that
+//! do not fit their width are truncated, and signed fields are sign-extended when unpacked.
+//! Whether the fields fit the chosen integer is checked at compile time.
+//!
+//! Besides the `Atomizable` implementation, the derive generates an `Atomic{Name}Fields`
+//! extension trait for `Atomic<Name>`, with `load_x`, `store_x` and `update_field_x` methods for
+//! every field `x`.
+//!
+//! # Examples
+#![cfg_attr(feature = "derive", doc = "```")]
+#![cfg_attr(not(feature = "derive"), doc = "```ignore")]
+//! use atomiq::prelude::*;
+//! # use atomiq::try_init_model;
+//!
+//! #[derive(Atomizable, Clone, Copy, Debug, PartialEq)]
+//! #[atomiq(repr = u16)]
+//! struct Slot {
+//!     #[atomiq(bits = 1)]
+//!     occupied: bool,
+//!     #[atomiq(bits = 4)]
+//!     generation: u8,
+//!     #[atomiq(bits = 11)]
+//!     index: u16,
+//! }
+//!
+//! # try_init_model(|| {
+//! let slot = Atomic::from(Slot { occupied: false, generation: 0, index: 0 });
+//!
+//! slot.store_index(42, Ordering::Relaxed);
+//! slot.update_field_generation(Ordering::Relaxed, Ordering::Relaxed, |generation| {
+//!     Some((generation + 1) % 16)
+//! }).unwrap();
+//!
+//! assert_eq!(slot.load(Ordering::Relaxed), Slot { occupied: false, generation: 1, index: 42 });
+//! assert_eq!(slot.load_generation(Ordering::Relaxed), 1);
+//! # });
+//! ```
+//!
+//! Enums with fields are packed as tagged unions in the same way: the index of the variant is
+//! stored in the lowest bits, followed by the fields of the variant. Unused bits are always zero,
+//! so equal values have equal atoms and `compare_exchange` compares whole variants.
+//!
+#![cfg_attr(feature = "derive", doc = "```")]
+#![cfg_attr(not(feature = "derive"), doc = "```ignore")]
+//! use atomiq::prelude::*;
+//! # use atomiq::try_init_model;
+//!
+//! #[derive(Atomizable, Clone, Copy, Debug, PartialEq)]
+//! enum Conn {
+//!     Idle,
+//!     Connecting(u16),
+//!     Open { fd: u32 },
+//! }
+//!
+//! # try_init_model(|| {
+//! let conn = Atomic::from(Conn::Connecting(80));
+//! let result = conn.compare_exchange(
+//!     Conn::Connecting(80),
+//!     Conn::Open { fd: 3 },
+//!     Ordering::AcqRel,
+//!     Ordering::Acquire,
+//! );
+//!
+//! assert_eq!(result, Ok(Conn::Connecting(80)));
+//! assert_eq!(conn.load(Ordering::Acquire), Conn::Open { fd: 3 });
+//! # });
+//! ```
+//!
+//! See [`BitField`] for more information.
+
+use crate::prelude::*;
+
+/// Trait for primitive atoms that may be packed into a range of bits.
+pub trait BitField: Atom {
+    /// The width of the atom in bits.
+    const BITS: u32;
+
+    /// Converts the atom into bits, sign-extending signed integers.
+    fn to_bits(self) -> u64;
+
+    /// Converts the lowest `width` bits back into the atom, sign-extending signed integers.
+    fn from_bits(bits: u64, width: u32) -> Self;
+}
+
+impl BitField for bool {
+    const BITS: u32 = 1;
+
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+
+    fn from_bits(bits: u64, _width: u32) -> Self {
+        bits & 1 != 0
+    }
+}
+
+macro_rules! bit_field_impls {
+    (unsigned $($ty:ty),+) => {
+        $(
+            impl BitField for $ty {
+                const BITS: u32 = <$ty>::BITS;
+
+                fn to_bits(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_bits(bits: u64, _width: u32) -> Self {
+                    bits as Self
+                }
+            }
+        )+
+    };
+    (signed $($ty:ty),+) => {
+        $(
+            impl BitField for $ty {
+                const BITS: u32 = <$ty>::BITS;
+
+                fn to_bits(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_bits(bits: u64, width: u32) -> Self {
+                    let shift = u64::BITS - width;
+                    (((bits << shift) as i64) >> shift) as Self
+                }
+            }
+        )+
+    };
+}
+
+bit_field_impls!(unsigned u8, u16, u32, u64, usize);
+bit_field_impls!(signed i8, i16, i32, i64, isize);
+
+/// Returns a mask of the lowest `width` bits.
+#[doc(hidden)]
+pub const fn mask(width: u32) -> u64 {
+    if width >= u64::BITS {
+        u64::MAX
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// Returns the `width` bits of `bits` starting at `offset`.
+#[doc(hidden)]
+pub const fn extract(bits: u64, offset: u32, width: u32) -> u64 {
+    (bits >> offset) & mask(width)
+}
+
+/// Replaces the `width` bits of `bits` starting at `offset` with the lowest bits of `value`.
+#[doc(hidden)]
+pub const fn insert(bits: u64, offset: u32, width: u32, value: u64) -> u64 {
+    let mask = mask(width) << offset;
+    (bits & !mask) | ((value << offset) & mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_insert_extract() {
+        let bits = insert(0, 4, 4, 0xff);
+        assert_eq!(bits, 0xf0);
+        assert_eq!(extract(bits, 4, 4), 0xf);
+        assert_eq!(extract(insert(u64::MAX, 0, 64, 3), 0, 64), 3);
+        assert_eq!(insert(0xff, 2, 2, 0), 0xf3);
+    }
+
+    #[test]
+    fn test_sign_extension() {
+        let bits = extract((-3i8).to_bits(), 0, 3);
+        assert_eq!(bits, 0b101);
+        assert_eq!(i8::from_bits(bits, 3), -3);
+        assert_eq!(i64::from_bits(u64::MAX, 64), -1);
+        assert_eq!(u8::from_bits(0b101, 3), 5);
+    }
+}
@@ -0,0 +1,495 @@
+//! Atomic bit sets spanning multiple words.
+//!
+//! See [`AtomicBitSet`] and [`AtomicBitVec`] for more information.
+
+pub use crate::__atomic_bit_set as atomic_bit_set;
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::fmt::{self, Debug, Formatter};
+use crate::prelude::*;
+
+const WORD_BITS: usize = usize::BITS as usize;
+
+/// Names the type of an [`AtomicBitSet`] holding the given number of bits.
+///
+/// `atomic_bit_set!(BITS)` is `AtomicBitSet<BITS, { words_for(BITS) }>`, which a type alias
+/// cannot express on stable Rust.
+///
+/// Use it through [`atomiq::bitset::atomic_bit_set!`](crate::bitset::atomic_bit_set).
+///
+/// # Examples
+/// ```
+/// use atomiq::bitset::{atomic_bit_set, AtomicBitSet};
+/// # use atomiq::try_init_model;
+///
+/// struct Table {
+///     slots: atomic_bit_set!(1000),
+/// }
+///
+/// # try_init_model(|| {
+/// let table = Table { slots: AtomicBitSet::new() };
+/// assert_eq!(table.slots.len(), 1000);
+/// # });
+/// ```
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __atomic_bit_set {
+    ($bits:expr) => {
+        $crate::bitset::AtomicBitSet<{ $bits }, { $crate::bitset::words_for($bits) }>
+    };
+}
+
+/// Returns the number of words needed to hold the given number of bits.
+///
+/// Usually used through [`atomic_bit_set!`].
+///
+/// # Examples
+/// ```
+/// use atomiq::bitset::{words_for, AtomicBitSet};
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let slots: AtomicBitSet<1000, { words_for(1000) }> = AtomicBitSet::new();
+/// assert_eq!(slots.len(), 1000);
+/// # });
+/// ```
+///
+/// Any other word count is rejected:
+/// ```compile_fail
+/// use atomiq::bitset::AtomicBitSet;
+///
+/// let slots: AtomicBitSet<1000, 1> = AtomicBitSet::new();
+/// ```
+pub const fn words_for(bits: usize) -> usize {
+    bits.div_ceil(WORD_BITS)
+}
+
+/// A view of the words of a bit set, of which only the first `len` bits are used.
+#[derive(Clone, Copy)]
+struct Bits<'a> {
+    words: &'a [Atomic<usize>],
+    len: usize,
+}
+
+impl<'a> Bits<'a> {
+    fn locate(self, index: usize) -> (&'a Atomic<usize>, usize) {
+        assert!(index < self.len, "bit index {index} out of range for length {}", self.len);
+        (&self.words[index / WORD_BITS], 1 << (index % WORD_BITS))
+    }
+
+    /// Returns the mask of the used bits of the given word.
+    fn mask(self, word: usize) -> usize {
+        let used = self.len - word * WORD_BITS;
+        if used >= WORD_BITS {
+            usize::MAX
+        } else {
+            (1 << used) - 1
+        }
+    }
+
+    fn test(self, index: usize, ordering: Ordering) -> bool {
+        let (word, bit) = self.locate(index);
+        word.load(ordering) & bit != 0
+    }
+
+    fn test_and_set(self, index: usize, ordering: Ordering) -> bool {
+        let (word, bit) = self.locate(index);
+        word.fetch_or(bit, ordering) & bit != 0
+    }
+
+    fn test_and_clear(self, index: usize, ordering: Ordering) -> bool {
+        let (word, bit) = self.locate(index);
+        word.fetch_and(!bit, ordering) & bit != 0
+    }
+
+    fn test_and_toggle(self, index: usize, ordering: Ordering) -> bool {
+        let (word, bit) = self.locate(index);
+        word.fetch_xor(bit, ordering) & bit != 0
+    }
+
+    fn find_first_clear_and_set(self, ordering: Ordering) -> Option<usize> {
+        for (i, word) in self.words.iter().enumerate() {
+            let mask = self.mask(i);
+            let mut value = word.load(Ordering::Relaxed);
+            while value & mask != mask {
+                let bit = 1 << (!value).trailing_zeros();
+                let previous = word.fetch_or(bit, ordering);
+                if previous & bit == 0 {
+                    return Some(i * WORD_BITS + bit.trailing_zeros() as usize);
+                }
+                // Another thread took this bit first, try the next clear one.
+                value = previous;
+            }
+        }
+        None
+    }
+
+    fn count_ones(self, ordering: Ordering) -> usize {
+        self.words.iter()
+            .enumerate()
+            .map(|(i, word)| (word.load(ordering) & self.mask(i)).count_ones() as usize)
+            .sum()
+    }
+
+    fn clear_all(self, ordering: Ordering) {
+        for word in self.words {
+            word.store(0, ordering);
+        }
+    }
+
+    fn iter(self, ordering: Ordering) -> Iter<'a> {
+        Iter {
+            bits: self,
+            ordering,
+            word: 0,
+            current: self.words.first().map_or(0, |word| word.load(ordering) & self.mask(0)),
+        }
+    }
+
+    fn fmt_named(self, name: &str, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{name}")?;
+        f.debug_set().entries(self.iter(Ordering::Relaxed)).finish()
+    }
+}
+
+impl Debug for Bits<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bits").field("len", &self.len).finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the indices of the set bits of a bit set.
+///
+/// Each word is loaded once, when the iterator reaches it, so concurrent changes may or may not
+/// be observed.
+#[derive(Debug)]
+pub struct Iter<'a> {
+    bits: Bits<'a>,
+    ordering: Ordering,
+    word: usize,
+    current: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.word += 1;
+            let word = self.bits.words.get(self.word)?;
+            self.current = word.load(self.ordering) & self.bits.mask(self.word);
+        }
+
+        let bit = self.current.trailing_zeros() as usize;
+        // Clear the lowest set bit.
+        self.current &= self.current - 1;
+        Some(self.word * WORD_BITS + bit)
+    }
+}
+
+macro_rules! bitset_methods {
+    () => {
+        /// Returns the number of bits in the set.
+        pub fn len(&self) -> usize {
+            self.bits().len
+        }
+
+        /// Returns whether the set has no bits at all.
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Returns whether the bit at the given index is set.
+        ///
+        /// # Panics
+        /// Panics if the index is out of range.
+        pub fn test(&self, index: usize, ordering: Ordering) -> bool {
+            self.bits().test(index, ordering)
+        }
+
+        /// Sets the bit at the given index.
+        ///
+        /// # Panics
+        /// Panics if the index is out of range.
+        pub fn set(&self, index: usize, ordering: Ordering) {
+            self.bits().test_and_set(index, ordering);
+        }
+
+        /// Clears the bit at the given index.
+        ///
+        /// # Panics
+        /// Panics if the index is out of range.
+        pub fn clear(&self, index: usize, ordering: Ordering) {
+            self.bits().test_and_clear(index, ordering);
+        }
+
+        /// Sets the bit at the given index, returning whether it was set before.
+        ///
+        /// # Panics
+        /// Panics if the index is out of range.
+        pub fn test_and_set(&self, index: usize, ordering: Ordering) -> bool {
+            self.bits().test_and_set(index, ordering)
+        }
+
+        /// Clears the bit at the given index, returning whether it was set before.
+        ///
+        /// # Panics
+        /// Panics if the index is out of range.
+        pub fn test_and_clear(&self, index: usize, ordering: Ordering) -> bool {
+            self.bits().test_and_clear(index, ordering)
+        }
+
+        /// Flips the bit at the given index, returning whether it was set before.
+        ///
+        /// # Panics
+        /// Panics if the index is out of range.
+        pub fn test_and_toggle(&self, index: usize, ordering: Ordering) -> bool {
+            self.bits().test_and_toggle(index, ordering)
+        }
+
+        /// Finds the first clear bit and sets it, returning its index.
+        ///
+        /// This allocates a slot atomically: no two threads get the same index until it is
+        /// cleared again. Returns `None` if all bits are set.
+        pub fn find_first_clear_and_set(&self, ordering: Ordering) -> Option<usize> {
+            self.bits().find_first_clear_and_set(ordering)
+        }
+
+        /// Counts the set bits.
+        ///
+        /// Each word is loaded separately, so the result is not a snapshot of the whole set.
+        pub fn count_ones(&self, ordering: Ordering) -> usize {
+            self.bits().count_ones(ordering)
+        }
+
+        /// Clears all bits.
+        ///
+        /// Each word is stored separately, so concurrent changes to other words may survive.
+        pub fn clear_all(&self, ordering: Ordering) {
+            self.bits().clear_all(ordering)
+        }
+
+        /// Returns an iterator over the indices of the set bits, in ascending order.
+        pub fn iter(&self, ordering: Ordering) -> Iter<'_> {
+            self.bits().iter(ordering)
+        }
+    };
+}
+
+/// A fixed-size atomic bit set.
+///
+/// The set holds exactly `BITS` bits, stored in `WORDS` machine words. Stable Rust cannot compute
+/// the array length from `BITS`, so the type is named with
+/// [`atomic_bit_set!(BITS)`](atomic_bit_set), which fills in `WORDS` as
+/// [`words_for(BITS)`](words_for); any other word count fails to compile.
+///
+/// Operations on a single bit are lock-free and map to a single atomic instruction. Operations
+/// on the whole set work word by word, so they are not atomic as a whole.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::bitset::{atomic_bit_set, AtomicBitSet};
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let slots: atomic_bit_set!(128) = AtomicBitSet::new();
+///
+/// assert_eq!(slots.find_first_clear_and_set(Ordering::Acquire), Some(0));
+/// assert_eq!(slots.find_first_clear_and_set(Ordering::Acquire), Some(1));
+///
+/// slots.set(100, Ordering::Relaxed);
+/// slots.clear(0, Ordering::Release);
+///
+/// assert!(slots.iter(Ordering::Relaxed).eq([1, 100]));
+/// assert_eq!(slots.count_ones(Ordering::Relaxed), 2);
+/// # });
+/// ```
+pub struct AtomicBitSet<const BITS: usize, const WORDS: usize> {
+    words: [Atomic<usize>; WORDS],
+}
+
+impl<const BITS: usize, const WORDS: usize> AtomicBitSet<BITS, WORDS> {
+    /// Creates a new bit set with all bits cleared.
+    pub fn new() -> Self {
+        const { assert!(WORDS == words_for(BITS), "`WORDS` must be `words_for(BITS)`") };
+        Self {
+            words: core::array::from_fn(|_| Atomic::default()),
+        }
+    }
+
+    fn bits(&self) -> Bits<'_> {
+        Bits {
+            words: &self.words,
+            len: BITS,
+        }
+    }
+
+    bitset_methods!();
+}
+
+impl<const BITS: usize, const WORDS: usize> Default for AtomicBitSet<BITS, WORDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const BITS: usize, const WORDS: usize> Debug for AtomicBitSet<BITS, WORDS> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.bits().fmt_named("AtomicBitSet", f)
+    }
+}
+
+/// A heap-allocated atomic bit set with a length chosen at runtime.
+///
+/// Works like [`AtomicBitSet`], but holds exactly the requested number of bits.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::bitset::AtomicBitVec;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let slots = AtomicBitVec::new(2);
+///
+/// assert_eq!(slots.find_first_clear_and_set(Ordering::Acquire), Some(0));
+/// assert_eq!(slots.find_first_clear_and_set(Ordering::Acquire), Some(1));
+/// assert_eq!(slots.find_first_clear_and_set(Ordering::Acquire), None);
+/// # });
+/// ```
+#[cfg(feature = "alloc")]
+pub struct AtomicBitVec {
+    words: Box<[Atomic<usize>]>,
+    len: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl AtomicBitVec {
+    /// Creates a new bit set of the given length with all bits cleared.
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: (0..words_for(len)).map(|_| Atomic::default()).collect(),
+            len,
+        }
+    }
+
+    fn bits(&self) -> Bits<'_> {
+        Bits {
+            words: &self.words,
+            len: self.len,
+        }
+    }
+
+    bitset_methods!();
+}
+
+#[cfg(feature = "alloc")]
+impl Debug for AtomicBitVec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.bits().fmt_named("AtomicBitVec", f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+    #[cfg(feature = "loom")]
+    use loom::thread;
+
+    #[test]
+    fn test_atomic_bit_set_sync() {
+        try_init_model(|| {
+            let set: AtomicBitSet<{ 2 * WORD_BITS }, 2> = AtomicBitSet::new();
+            assert_eq!(set.len(), 2 * WORD_BITS);
+
+            assert!(!set.test_and_set(3, Ordering::Relaxed));
+            assert!(set.test_and_set(3, Ordering::Relaxed));
+            set.set(WORD_BITS + 1, Ordering::Relaxed);
+            assert!(set.test(WORD_BITS + 1, Ordering::Relaxed));
+            assert!(!set.test_and_toggle(0, Ordering::Relaxed));
+
+            assert!(set.iter(Ordering::Relaxed).eq([0, 3, WORD_BITS + 1]));
+            assert_eq!(set.count_ones(Ordering::Relaxed), 3);
+
+            assert!(set.test_and_clear(3, Ordering::Relaxed));
+            assert!(!set.test(3, Ordering::Relaxed));
+            set.clear_all(Ordering::Relaxed);
+            assert_eq!(set.iter(Ordering::Relaxed).next(), None);
+        });
+    }
+
+    #[test]
+    fn test_atomic_bit_set_find_across_words() {
+        try_init_model(|| {
+            let set: AtomicBitSet<{ 2 * WORD_BITS }, 2> = AtomicBitSet::new();
+            for i in 0..WORD_BITS - 1 {
+                set.set(i, Ordering::Relaxed);
+            }
+
+            assert_eq!(set.find_first_clear_and_set(Ordering::Relaxed), Some(WORD_BITS - 1));
+            assert_eq!(set.find_first_clear_and_set(Ordering::Relaxed), Some(WORD_BITS));
+            assert_eq!(set.count_ones(Ordering::Relaxed), WORD_BITS + 1);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_atomic_bit_vec_len() {
+        try_init_model(|| {
+            let set = AtomicBitVec::new(WORD_BITS + 2);
+            for _ in 0..WORD_BITS + 2 {
+                assert!(set.find_first_clear_and_set(Ordering::Relaxed).is_some());
+            }
+
+            // The unused bits of the last word are never handed out.
+            assert_eq!(set.find_first_clear_and_set(Ordering::Relaxed), None);
+            assert_eq!(set.count_ones(Ordering::Relaxed), WORD_BITS + 2);
+            assert_eq!(set.iter(Ordering::Relaxed).last(), Some(WORD_BITS + 1));
+        });
+    }
+
+    #[test]
+    fn test_atomic_bit_set_len() {
+        try_init_model(|| {
+            let set: atomic_bit_set!(WORD_BITS + 2) = AtomicBitSet::new();
+            assert_eq!(set.len(), WORD_BITS + 2);
+            for _ in 0..WORD_BITS + 2 {
+                assert!(set.find_first_clear_and_set(Ordering::Relaxed).is_some());
+            }
+
+            // The unused bits of the last word are never handed out.
+            assert_eq!(set.find_first_clear_and_set(Ordering::Relaxed), None);
+            assert_eq!(set.count_ones(Ordering::Relaxed), WORD_BITS + 2);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_atomic_bit_set_out_of_range() {
+        try_init_model(|| {
+            let set: AtomicBitSet<10, 1> = AtomicBitSet::new();
+            set.set(10, Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_atomic_bit_vec_loom_allocation() {
+        try_init_model(|| {
+            let set: Arc<AtomicBitVec> = Arc::new(AtomicBitVec::new(2));
+
+            let threads: [_; 2] = core::array::from_fn(|_| thread::spawn({
+                let set = set.clone();
+                move || set.find_first_clear_and_set(Ordering::AcqRel).unwrap()
+            }));
+
+            let [a, b] = threads.map(|thread| thread.join().unwrap());
+            assert_ne!(a, b);
+            assert_eq!(set.find_first_clear_and_set(Ordering::AcqRel), None);
+        });
+    }
+}
@@ -0,0 +1,336 @@
+//! Atomically exchangeable `Box`es.
+//!
+//! See [`AtomicBox`] and [`AtomicOptionBox`] for more information.
+
+use alloc::boxed::Box;
+use core::fmt::{self, Debug, Formatter};
+use core::marker::PhantomData;
+use core::ptr;
+use crate::prelude::*;
+
+/// An atomic `Option<Box<T>>`.
+///
+/// This is a lock-free slot for handing an owned, heap-allocated value from one thread to
+/// another. Unlike [`AtomicOption`](crate::option::AtomicOption), the value is never copied:
+/// exactly one thread gets to take it out.
+///
+/// Operations that hand out a value acquire it and operations that put a value in release it,
+/// so the contents of the box are always visible to the thread that takes it.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::boxed::AtomicOptionBox;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let slot: AtomicOptionBox<i32> = AtomicOptionBox::none();
+///
+/// assert!(slot.compare_exchange_null(Box::new(42)).is_ok());
+/// assert_eq!(slot.compare_exchange_null(Box::new(43)), Err(Box::new(43)));
+///
+/// assert_eq!(slot.take(), Some(Box::new(42)));
+/// assert_eq!(slot.take(), None);
+/// # });
+/// ```
+pub struct AtomicOptionBox<T> {
+    ptr: Atomic<*mut T>,
+    _marker: PhantomData<Box<T>>,
+}
+
+// Sharing the slot allows moving the value to another thread, but never sharing it.
+unsafe impl<T: Send> Send for AtomicOptionBox<T> {}
+unsafe impl<T: Send> Sync for AtomicOptionBox<T> {}
+
+impl<T> AtomicOptionBox<T> {
+    /// Creates a new atomic option with the given value.
+    pub fn new(value: Option<Box<T>>) -> Self {
+        Self {
+            ptr: Atomic::from(Self::into_ptr(value)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a new atomic option with no value.
+    pub fn none() -> Self {
+        Self::new(None)
+    }
+
+    /// Creates a new atomic option with a value.
+    pub fn some(value: Box<T>) -> Self {
+        Self::new(Some(value))
+    }
+
+    fn into_ptr(value: Option<Box<T>>) -> *mut T {
+        value.map_or(ptr::null_mut(), Box::into_raw)
+    }
+
+    /// Takes back the ownership of a pointer obtained from [`Self::into_ptr`].
+    ///
+    /// # Safety
+    /// The pointer must be null or own its allocation, which is consumed.
+    unsafe fn from_ptr(ptr: *mut T) -> Option<Box<T>> {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Box::from_raw(ptr))
+        }
+    }
+
+    /// Returns whether the option is `Some`.
+    pub fn is_some(&self) -> bool {
+        !self.ptr.load(Ordering::Acquire).is_null()
+    }
+
+    /// Returns whether the option is `None`.
+    pub fn is_none(&self) -> bool {
+        !self.is_some()
+    }
+
+    /// Stores a value, dropping the previous one.
+    pub fn store(&self, value: Option<Box<T>>) {
+        self.swap(value);
+    }
+
+    /// Stores a value, returning the previous one.
+    pub fn swap(&self, value: Option<Box<T>>) -> Option<Box<T>> {
+        let previous = self.ptr.swap(Self::into_ptr(value), Ordering::AcqRel);
+        // The allocation owned by the atomic is now ours.
+        unsafe { Self::from_ptr(previous) }
+    }
+
+    /// Takes the value out, leaving `None` in its place.
+    pub fn take(&self) -> Option<Box<T>> {
+        self.swap(None)
+    }
+
+    /// Stores a value only if the option is `None`.
+    ///
+    /// If the option already holds a value, `new` is given back.
+    pub fn compare_exchange_null(&self, new: Box<T>) -> Result<(), Box<T>> {
+        let new = Box::into_raw(new);
+        self.ptr.compare_exchange(ptr::null_mut(), new, Ordering::Release, Ordering::Relaxed)
+            .map(|_| ())
+            // The exchange failed, so the allocation is still ours.
+            .map_err(|_| unsafe { Box::from_raw(new) })
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// This is safe because the mutable reference guarantees that no other thread accesses it.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.ptr.load(Ordering::Acquire).as_mut() }
+    }
+
+    /// Consumes the atomic option, returning the contained value.
+    pub fn into_inner(self) -> Option<Box<T>> {
+        self.take()
+    }
+}
+
+impl<T> Drop for AtomicOptionBox<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Self::from_ptr(self.ptr.load(Ordering::Acquire)) });
+    }
+}
+
+impl<T> Default for AtomicOptionBox<T> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<T> From<Option<Box<T>>> for AtomicOptionBox<T> {
+    fn from(value: Option<Box<T>>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T> From<Box<T>> for AtomicOptionBox<T> {
+    fn from(value: Box<T>) -> Self {
+        Self::some(value)
+    }
+}
+
+impl<T> Debug for AtomicOptionBox<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // The value itself may be taken and dropped by another thread at any time.
+        f.debug_tuple("AtomicOptionBox").field(&self.ptr.load(Ordering::Relaxed)).finish()
+    }
+}
+
+/// An atomic `Box<T>`.
+///
+/// Like [`AtomicOptionBox`], but only ever stores values. It starts out holding one, and
+/// becomes empty once the value is [taken](Self::take), so the operations handing out the
+/// previous value still return an `Option`.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::boxed::AtomicBox;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let current: AtomicBox<&str> = AtomicBox::from(Box::new("first"));
+///
+/// assert_eq!(current.swap(Box::new("second")), Some(Box::new("first")));
+/// assert_eq!(current.take(), Some(Box::new("second")));
+/// assert_eq!(current.take(), None);
+///
+/// assert!(current.compare_exchange_null(Box::new("third")).is_ok());
+/// assert_eq!(current.into_inner(), Some(Box::new("third")));
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct AtomicBox<T>(AtomicOptionBox<T>);
+
+impl<T> AtomicBox<T> {
+    /// Creates a new atomic `Box` with the given value.
+    pub fn new(value: Box<T>) -> Self {
+        Self(AtomicOptionBox::some(value))
+    }
+
+    /// Returns whether the value has been taken out.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Stores a value, dropping the previous one.
+    pub fn store(&self, value: Box<T>) {
+        self.0.store(Some(value));
+    }
+
+    /// Stores a value, returning the previous one, if it has not been taken.
+    pub fn swap(&self, value: Box<T>) -> Option<Box<T>> {
+        self.0.swap(Some(value))
+    }
+
+    /// Takes the value out, leaving the atomic `Box` empty.
+    pub fn take(&self) -> Option<Box<T>> {
+        self.0.take()
+    }
+
+    /// Stores a value only if the previous one has been taken.
+    ///
+    /// If the atomic `Box` still holds a value, `new` is given back.
+    pub fn compare_exchange_null(&self, new: Box<T>) -> Result<(), Box<T>> {
+        self.0.compare_exchange_null(new)
+    }
+
+    /// Returns a mutable reference to the value, if it has not been taken.
+    ///
+    /// This is safe because the mutable reference guarantees that no other thread accesses it.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.0.get_mut()
+    }
+
+    /// Consumes the atomic `Box`, returning the contained value, if it has not been taken.
+    pub fn into_inner(self) -> Option<Box<T>> {
+        self.0.into_inner()
+    }
+}
+
+impl<T: Default> Default for AtomicBox<T> {
+    fn default() -> Self {
+        Self::new(Box::default())
+    }
+}
+
+impl<T> From<Box<T>> for AtomicBox<T> {
+    fn from(value: Box<T>) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+    #[cfg(feature = "loom")]
+    use loom::thread;
+
+    #[test]
+    fn test_atomic_option_box_sync() {
+        try_init_model(|| {
+            let slot: AtomicOptionBox<i32> = AtomicOptionBox::none();
+            assert!(slot.is_none());
+            assert_eq!(slot.take(), None);
+
+            assert_eq!(slot.compare_exchange_null(Box::new(1)), Ok(()));
+            assert_eq!(slot.compare_exchange_null(Box::new(2)), Err(Box::new(2)));
+            assert!(slot.is_some());
+
+            assert_eq!(slot.swap(Some(Box::new(3))), Some(Box::new(1)));
+            assert_eq!(slot.take(), Some(Box::new(3)));
+            assert!(slot.is_none());
+        });
+    }
+
+    #[test]
+    fn test_atomic_box_drop() {
+        try_init_model(|| {
+            let value = Arc::new(1);
+
+            let boxed = AtomicBox::new(Box::new(value.clone()));
+            assert_eq!(Arc::strong_count(&value), 2);
+
+            boxed.store(Box::new(Arc::new(2)));
+            assert_eq!(Arc::strong_count(&value), 1);
+
+            boxed.store(Box::new(value.clone()));
+            drop(boxed);
+            assert_eq!(Arc::strong_count(&value), 1);
+        });
+    }
+
+    #[test]
+    fn test_atomic_box_take() {
+        try_init_model(|| {
+            let boxed = AtomicBox::new(Box::new(1));
+            assert!(!boxed.is_empty());
+            assert_eq!(boxed.compare_exchange_null(Box::new(2)), Err(Box::new(2)));
+
+            assert_eq!(boxed.take(), Some(Box::new(1)));
+            assert!(boxed.is_empty());
+            assert_eq!(boxed.swap(Box::new(3)), None);
+
+            assert_eq!(boxed.take(), Some(Box::new(3)));
+            assert_eq!(boxed.compare_exchange_null(Box::new(4)), Ok(()));
+            assert_eq!(boxed.into_inner(), Some(Box::new(4)));
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_atomic_option_box_loom_hand_off() {
+        try_init_model(|| {
+            let slot: Arc<AtomicOptionBox<i32>> = Arc::new(AtomicOptionBox::none());
+
+            let producer = thread::spawn({
+                let slot = slot.clone();
+                move || {
+                    slot.compare_exchange_null(Box::new(42)).unwrap();
+                }
+            });
+
+            let consumer = thread::spawn({
+                let slot = slot.clone();
+                move || slot.take()
+            });
+
+            producer.join().unwrap();
+            let taken = consumer.join().unwrap();
+
+            // Exactly one side ends up with the value.
+            match taken {
+                Some(value) => {
+                    assert_eq!(*value, 42);
+                    assert!(slot.is_none());
+                }
+                None => assert_eq!(slot.take(), Some(Box::new(42))),
+            }
+        });
+    }
+}
@@ -4,6 +4,9 @@ use crate::prelude::*;
 /// A token that can be used to cancel an async operation.
 ///
 /// Passed by reference to any async operation that should be cancellable.
+///
+/// Cancelling releases and checking acquires, so everything done before cancelling is visible
+/// to the operation once it sees the cancellation.
 #[derive(Debug, Default)]
 #[repr(transparent)]
 pub struct CancellationToken(Atomic<bool>);
@@ -15,6 +18,7 @@ impl CancellationToken {
     }
 }
 
+/// Trait for types that can be used to cancel an operation.
 pub trait Cancel {
     /// Cancels the operation associated with the token.
     ///
@@ -31,15 +35,15 @@ pub trait Cancel {
 
 impl Cancel for CancellationToken {
     fn cancel(&self) {
-        self.0.store(true, Ordering::Relaxed);
+        self.0.store(true, Ordering::Release);
     }
 
     fn fetch_cancel(&self) -> bool {
-        self.0.swap(true, Ordering::Relaxed)
+        self.0.swap(true, Ordering::AcqRel)
     }
 
     fn is_cancelled(&self) -> bool {
-        self.0.load(Ordering::Relaxed)
+        self.0.load(Ordering::Acquire)
     }
 }
 
@@ -81,35 +85,42 @@ impl <T: Deref<Target = CancellationToken>> Cancel for Option<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::try_init_model;
 
     #[test]
     fn test_cancellation_token() {
-        let token = CancellationToken::new();
-        assert!(!token.is_cancelled());
+        try_init_model(|| {
+            let token = CancellationToken::new();
+            assert!(!token.is_cancelled());
 
-        token.cancel();
-        assert!(token.is_cancelled());
+            token.cancel();
+            assert!(token.is_cancelled());
+        });
     }
 
     #[test]
     fn test_option_cancellation_token_some() {
-        let token = CancellationToken::new();
+        try_init_model(|| {
+            let token = CancellationToken::new();
 
-        let opt_token = Some(&token);
+            let opt_token = Some(&token);
 
-        assert!(!opt_token.is_cancelled());
-        assert!(!opt_token.fetch_cancel());
-        assert!(opt_token.is_cancelled());
-        assert!(opt_token.fetch_cancel());
+            assert!(!opt_token.is_cancelled());
+            assert!(!opt_token.fetch_cancel());
+            assert!(opt_token.is_cancelled());
+            assert!(opt_token.fetch_cancel());
+        });
     }
 
     #[test]
     fn test_option_cancellation_token_none() {
-        let opt_token: Option<&CancellationToken> = None;
-
-        assert!(!opt_token.is_cancelled());
-        assert!(!opt_token.fetch_cancel());
-        assert!(!opt_token.is_cancelled());
-        assert!(!opt_token.fetch_cancel());
+        try_init_model(|| {
+            let opt_token: Option<&CancellationToken> = None;
+
+            assert!(!opt_token.is_cancelled());
+            assert!(!opt_token.fetch_cancel());
+            assert!(!opt_token.is_cancelled());
+            assert!(!opt_token.fetch_cancel());
+        });
     }
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+use cfg_if::cfg_if;
+
+cfg_if!(
+    if #[cfg(feature = "loom")] {
+        pub use loom::cell::{ConstPtr, MutPtr, UnsafeCell};
+    } else {
+        /// An `UnsafeCell` with the access API of `loom::cell::UnsafeCell`.
+        #[derive(Debug, Default)]
+        pub struct UnsafeCell<T: ?Sized>(core::cell::UnsafeCell<T>);
+
+        /// An immutable pointer into an [`UnsafeCell`].
+        #[derive(Debug)]
+        pub struct ConstPtr<T: ?Sized>(*const T);
+
+        /// A mutable pointer into an [`UnsafeCell`].
+        #[derive(Debug)]
+        pub struct MutPtr<T: ?Sized>(*mut T);
+
+        impl<T> UnsafeCell<T> {
+            /// Creates a new cell containing the value.
+            pub const fn new(data: T) -> Self {
+                Self(core::cell::UnsafeCell::new(data))
+            }
+
+            /// Unwraps the value.
+            pub fn into_inner(self) -> T {
+                self.0.into_inner()
+            }
+        }
+
+        impl<T: ?Sized> UnsafeCell<T> {
+            /// Calls `f` with an immutable pointer to the value.
+            pub fn with<F, R>(&self, f: F) -> R
+            where
+                F: FnOnce(*const T) -> R,
+            {
+                f(self.0.get())
+            }
+
+            /// Calls `f` with a mutable pointer to the value.
+            pub fn with_mut<F, R>(&self, f: F) -> R
+            where
+                F: FnOnce(*mut T) -> R,
+            {
+                f(self.0.get())
+            }
+
+            /// Returns an immutable pointer to the value.
+            pub fn get(&self) -> ConstPtr<T> {
+                ConstPtr(self.0.get())
+            }
+
+            /// Returns a mutable pointer to the value.
+            pub fn get_mut(&self) -> MutPtr<T> {
+                MutPtr(self.0.get())
+            }
+        }
+
+        impl<T: ?Sized> ConstPtr<T> {
+            /// Dereferences the pointer.
+            ///
+            /// # Safety
+            /// No mutable reference to the value may exist while the returned one is alive.
+            pub unsafe fn deref(&self) -> &T {
+                &*self.0
+            }
+        }
+
+        impl<T: ?Sized> MutPtr<T> {
+            /// Dereferences the pointer.
+            ///
+            /// # Safety
+            /// No other reference to the value may exist while the returned one is alive.
+            #[allow(clippy::mut_from_ref)]
+            pub unsafe fn deref(&self) -> &mut T {
+                &mut *self.0
+            }
+        }
+    }
+);
@@ -0,0 +1,530 @@
+//! Fair spin locks.
+//!
+//! Unlike [`SpinMutex`](crate::spin::SpinMutex), where whichever thread happens to win the race
+//! gets the lock, the locks in this module hand the lock out in the order the threads asked for
+//! it, so no thread can be starved.
+//!
+//! # Choosing a lock
+//! - [`TicketLock`] is two counters. It is tiny and simple, but all waiters spin on the same
+//!   counter, so every unlock invalidates the cache line of every waiter. It works best with
+//!   few cores contending.
+//! - [`McsLock`] queues waiters in a linked list of nodes, and each waiter spins on its own
+//!   node. An unlock only touches the next waiter, so it scales to many contending cores, at
+//!   the cost of a node per acquisition and a few more atomic operations when uncontended.
+//!
+//! With both locks, a waiter that gets preempted stalls everyone queued behind it, so they
+//! should not be used with more runnable threads than cores.
+
+use core::fmt::{self, Debug, Formatter};
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use crate::atom::spin_loop;
+use crate::cell::{MutPtr, UnsafeCell};
+use crate::prelude::*;
+
+/// A fair spin lock based on tickets.
+///
+/// Each thread takes a ticket and waits until that ticket is served, like at a deli counter.
+///
+/// # Examples
+/// ```
+/// use atomiq::fair::TicketLock;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let lock = TicketLock::new(0);
+///
+/// *lock.lock() += 1;
+///
+/// let guard = lock.lock();
+/// assert_eq!(*guard, 1);
+/// assert!(lock.try_lock().is_none());
+/// # });
+/// ```
+pub struct TicketLock<T> {
+    next_ticket: Atomic<u32>,
+    now_serving: Atomic<u32>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for TicketLock<T> {}
+
+impl<T> TicketLock<T> {
+    /// Creates a new unlocked lock.
+    pub fn new(value: T) -> Self {
+        Self {
+            next_ticket: Atomic::from(0),
+            now_serving: Atomic::from(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    fn guard(&self) -> TicketLockGuard<'_, T> {
+        TicketLockGuard {
+            lock: self,
+            data: ManuallyDrop::new(self.data.get_mut()),
+        }
+    }
+
+    /// Returns whether the lock is currently held.
+    pub fn is_locked(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed) != self.now_serving.load(Ordering::Relaxed)
+    }
+
+    /// Tries to lock without waiting.
+    ///
+    /// Only succeeds if nobody holds the lock or is waiting for it.
+    pub fn try_lock(&self) -> Option<TicketLockGuard<'_, T>> {
+        let serving = self.now_serving.load(Ordering::Acquire);
+        self.next_ticket
+            .compare_exchange(serving, serving.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed)
+            .ok()
+            .map(|_| self.guard())
+    }
+
+    /// Locks, spinning until all threads that asked for the lock before are done.
+    pub fn lock(&self) -> TicketLockGuard<'_, T> {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            spin_loop();
+        }
+        self.guard()
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// This is safe because the mutable reference guarantees that no other thread accesses it.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.with_mut(|ptr| unsafe { &mut *ptr })
+    }
+
+    /// Consumes the lock, returning the contained value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: Default> Default for TicketLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for TicketLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Debug> Debug for TicketLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("TicketLock");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+/// A guard that serves the next ticket of the [`TicketLock`] when dropped.
+pub struct TicketLockGuard<'a, T> {
+    lock: &'a TicketLock<T>,
+    data: ManuallyDrop<MutPtr<T>>,
+}
+
+unsafe impl<T: Sync> Sync for TicketLockGuard<'_, T> {}
+
+impl<T> Deref for TicketLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { MutPtr::deref(&self.data) }
+    }
+}
+
+impl<T> DerefMut for TicketLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { MutPtr::deref(&self.data) }
+    }
+}
+
+impl<T> Drop for TicketLockGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.data) };
+        self.lock.now_serving.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl<T: Debug> Debug for TicketLockGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// A queue node of an [`McsLock`].
+///
+/// Every acquisition needs its own node, which has to stay in place until the lock is released.
+/// Nodes can be reused once their guard is dropped, and are usually kept on the stack.
+///
+/// [`McsLock::with_lock`] and [`McsLock::try_with_lock`] manage the node themselves.
+#[derive(Debug)]
+pub struct McsNode {
+    next: Atomic<*mut McsNode>,
+    waiting: Atomic<bool>,
+}
+
+impl McsNode {
+    /// Creates a new node.
+    pub fn new() -> Self {
+        Self {
+            next: Atomic::from(ptr::null_mut()),
+            waiting: Atomic::from(false),
+        }
+    }
+
+    fn as_ptr(&self) -> *mut McsNode {
+        self as *const McsNode as *mut McsNode
+    }
+}
+
+impl Default for McsNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A fair spin lock based on the Mellor-Crummey and Scott queue.
+///
+/// Waiting threads form a queue of [`McsNode`]s, and each of them spins on its own node until
+/// the previous thread hands the lock over.
+///
+/// The lock keeps pointing at the node of the last thread in the queue until its guard is
+/// dropped, so locking with an explicit node is `unsafe`: forgetting the guard would leave the
+/// lock pointing at a node that may be freed. [`with_lock`](Self::with_lock) and
+/// [`try_with_lock`](Self::try_with_lock) keep the node and the guard to themselves, and are safe.
+///
+/// # Examples
+/// ```
+/// use atomiq::fair::{McsLock, McsNode};
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let lock = McsLock::new(0);
+///
+/// lock.with_lock(|value| *value += 1);
+///
+/// let mut node = McsNode::new();
+/// // Safety: the guard is dropped at the end of the statement.
+/// *unsafe { lock.lock(&mut node) } += 1;
+///
+/// assert_eq!(lock.try_with_lock(|value| *value), Some(2));
+/// # });
+/// ```
+pub struct McsLock<T> {
+    tail: Atomic<*mut McsNode>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for McsLock<T> {}
+
+impl<T> McsLock<T> {
+    /// Creates a new unlocked lock.
+    pub fn new(value: T) -> Self {
+        Self {
+            tail: Atomic::from(ptr::null_mut()),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    fn guard<'a>(&'a self, node: &'a McsNode) -> McsLockGuard<'a, T> {
+        McsLockGuard {
+            lock: self,
+            node,
+            data: ManuallyDrop::new(self.data.get_mut()),
+        }
+    }
+
+    /// Returns whether the lock is currently held.
+    pub fn is_locked(&self) -> bool {
+        !self.tail.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Tries to lock without waiting, using the given node.
+    ///
+    /// Only succeeds if nobody holds the lock or is waiting for it.
+    ///
+    /// # Safety
+    /// The returned guard must be dropped, and not forgotten, as the lock points at the node
+    /// until then.
+    pub unsafe fn try_lock<'a>(&'a self, node: &'a mut McsNode) -> Option<McsLockGuard<'a, T>> {
+        let node = &*node;
+        node.next.store(ptr::null_mut(), Ordering::Relaxed);
+
+        self.tail
+            .compare_exchange(ptr::null_mut(), node.as_ptr(), Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| self.guard(node))
+    }
+
+    /// Locks using the given node, spinning until all threads that asked for the lock before
+    /// are done.
+    ///
+    /// # Safety
+    /// The returned guard must be dropped, and not forgotten, as the lock points at the node
+    /// until then.
+    pub unsafe fn lock<'a>(&'a self, node: &'a mut McsNode) -> McsLockGuard<'a, T> {
+        // From now on other threads access the node too, so only share it.
+        let node = &*node;
+        node.next.store(ptr::null_mut(), Ordering::Relaxed);
+        node.waiting.store(true, Ordering::Relaxed);
+
+        let previous = self.tail.swap(node.as_ptr(), Ordering::AcqRel);
+        if !previous.is_null() {
+            // The previous node stays alive until it hands the lock over to us.
+            unsafe { (*previous).next.store(node.as_ptr(), Ordering::Release) };
+            while node.waiting.load(Ordering::Acquire) {
+                spin_loop();
+            }
+        }
+
+        self.guard(node)
+    }
+
+    /// Runs `f` with the lock held, using a node on the stack.
+    pub fn with_lock<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut node = McsNode::new();
+        // Safety: the guard never leaves this function, so it is dropped.
+        let mut guard = unsafe { self.lock(&mut node) };
+        f(&mut guard)
+    }
+
+    /// Runs `f` with the lock held if it can be locked without waiting, using a node on the
+    /// stack.
+    pub fn try_with_lock<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut node = McsNode::new();
+        // Safety: the guard never leaves this function, so it is dropped.
+        let mut guard = unsafe { self.try_lock(&mut node) }?;
+        Some(f(&mut guard))
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// This is safe because the mutable reference guarantees that no other thread accesses it.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.with_mut(|ptr| unsafe { &mut *ptr })
+    }
+
+    /// Consumes the lock, returning the contained value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: Default> Default for McsLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for McsLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Debug> Debug for McsLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("McsLock");
+        let unlocked = self.try_with_lock(|value| {
+            d.field("data", &&*value);
+        });
+        if unlocked.is_none() {
+            d.field("data", &format_args!("<locked>"));
+        }
+        d.finish()
+    }
+}
+
+/// A guard that hands the [`McsLock`] over to the next node in the queue when dropped.
+pub struct McsLockGuard<'a, T> {
+    lock: &'a McsLock<T>,
+    node: &'a McsNode,
+    data: ManuallyDrop<MutPtr<T>>,
+}
+
+unsafe impl<T: Sync> Sync for McsLockGuard<'_, T> {}
+
+impl<T> Deref for McsLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { MutPtr::deref(&self.data) }
+    }
+}
+
+impl<T> DerefMut for McsLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { MutPtr::deref(&self.data) }
+    }
+}
+
+impl<T> Drop for McsLockGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.data) };
+
+        let mut next = self.node.next.load(Ordering::Acquire);
+        if next.is_null() {
+            let unlocked = self.lock.tail
+                .compare_exchange(self.node.as_ptr(), ptr::null_mut(), Ordering::Release, Ordering::Relaxed)
+                .is_ok();
+            if unlocked {
+                return;
+            }
+
+            // Another thread has joined the queue, but has not linked its node to ours yet.
+            loop {
+                next = self.node.next.load(Ordering::Acquire);
+                if !next.is_null() {
+                    break;
+                }
+                spin_loop();
+            }
+        }
+
+        // The next node is waiting, so it is still alive. It may be gone right after this store.
+        unsafe { (*next).waiting.store(false, Ordering::Release) };
+    }
+}
+
+impl<T: Debug> Debug for McsLockGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+    #[cfg(feature = "loom")]
+    use alloc::vec::Vec;
+    #[cfg(feature = "loom")]
+    use loom::thread;
+
+    #[test]
+    fn test_ticket_lock_sync() {
+        try_init_model(|| {
+            let lock = TicketLock::new(1);
+
+            let mut guard = lock.lock();
+            assert!(lock.is_locked());
+            assert!(lock.try_lock().is_none());
+            *guard += 1;
+            drop(guard);
+
+            assert!(!lock.is_locked());
+            assert_eq!(*lock.try_lock().unwrap(), 2);
+            assert_eq!(lock.into_inner(), 2);
+        });
+    }
+
+    #[test]
+    fn test_mcs_lock_sync() {
+        try_init_model(|| {
+            let lock = McsLock::new(1);
+            let mut first = McsNode::new();
+            let mut second = McsNode::new();
+
+            let mut guard = unsafe { lock.lock(&mut first) };
+            assert!(lock.is_locked());
+            assert!(unsafe { lock.try_lock(&mut second) }.is_none());
+            assert_eq!(lock.try_with_lock(|value| *value), None);
+            *guard += 1;
+            drop(guard);
+
+            assert!(!lock.is_locked());
+            assert_eq!(*unsafe { lock.try_lock(&mut second) }.unwrap(), 2);
+            assert_eq!(lock.with_lock(|value| *value), 2);
+            assert_eq!(lock.into_inner(), 2);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_ticket_lock_loom_fifo() {
+        try_init_model(|| {
+            let lock: Arc<TicketLock<Vec<usize>>> = Arc::new(TicketLock::new(Vec::new()));
+            let guard = lock.lock();
+
+            let waiter = thread::spawn({
+                let lock = lock.clone();
+                move || lock.lock().push(1)
+            });
+            // Wait until the other thread has taken its ticket.
+            while lock.next_ticket.load(Ordering::Relaxed) != 2 {
+                spin_loop();
+            }
+
+            // Asking again right after unlocking must not overtake the waiting thread.
+            drop(guard);
+            lock.lock().push(0);
+
+            waiter.join().unwrap();
+            assert_eq!(*lock.lock(), [1, 0]);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_mcs_lock_loom_fifo() {
+        try_init_model(|| {
+            let lock: Arc<McsLock<Vec<usize>>> = Arc::new(McsLock::new(Vec::new()));
+            let mut node = McsNode::new();
+            let guard = unsafe { lock.lock(&mut node) };
+            let tail = lock.tail.load(Ordering::Relaxed);
+
+            let waiter = thread::spawn({
+                let lock = lock.clone();
+                move || lock.with_lock(|order| order.push(1))
+            });
+            // Wait until the other thread has joined the queue.
+            while lock.tail.load(Ordering::Relaxed) == tail {
+                spin_loop();
+            }
+
+            // Asking again right after unlocking must not overtake the waiting thread.
+            drop(guard);
+            lock.with_lock(|order| order.push(0));
+
+            waiter.join().unwrap();
+            assert_eq!(lock.with_lock(|order| order.clone()), [1, 0]);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_mcs_lock_loom_exclusion() {
+        try_init_model(|| {
+            let lock: Arc<McsLock<u32>> = Arc::new(McsLock::new(0));
+
+            let threads: [_; 2] = core::array::from_fn(|_| thread::spawn({
+                let lock = lock.clone();
+                move || lock.with_lock(|value| *value += 1)
+            }));
+
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(lock.with_lock(|value| *value), 2);
+        });
+    }
+}
@@ -0,0 +1,90 @@
+//! Typed sets of flags.
+//!
+//! See [`Flags`] for more information.
+
+use core::fmt::{self, Formatter};
+use crate::prelude::*;
+
+/// Trait for typed sets of flags stored in a single atom.
+///
+/// Usually implemented with `#[derive(AtomicFlags)]`, which generates a flag set type from an
+/// enum of flag names or from a struct of `bool` fields.
+///
+/// # Examples
+#[cfg_attr(feature = "derive", doc = "```")]
+#[cfg_attr(not(feature = "derive"), doc = "```ignore")]
+/// use atomiq::prelude::*;
+/// # use atomiq::try_init_model;
+///
+/// #[derive(AtomicFlags)]
+/// enum Permission {
+///     Read,
+///     Write,
+///     Execute,
+/// }
+///
+/// # try_init_model(|| {
+/// let permissions: Atomic<PermissionFlags> = Atomic::from(PermissionFlags::READ);
+///
+/// permissions.fetch_insert(PermissionFlags::WRITE | PermissionFlags::EXECUTE, Ordering::Relaxed);
+/// permissions.fetch_remove(PermissionFlags::READ, Ordering::Relaxed);
+///
+/// assert!(permissions.contains(PermissionFlags::WRITE, Ordering::Relaxed));
+/// assert_eq!(
+///     format!("{:?}", permissions.load(Ordering::Relaxed)),
+///     "PermissionFlags(WRITE | EXECUTE)",
+/// );
+/// # });
+/// ```
+pub trait Flags: BitAtomizable + Copy + 'static {
+    /// The names and values of the individual flags.
+    const FLAGS: &'static [(&'static str, Self)];
+
+    /// Returns whether all flags of `other` are also in `self`.
+    fn contains(self, other: Self) -> bool;
+
+    /// Returns the set of all known flags that are not in `self`.
+    fn complement(self) -> Self;
+}
+
+impl<T: Flags> Atomic<T> {
+    /// Adds the given flags, returning the previous set.
+    pub fn fetch_insert(&self, flags: T, ordering: Ordering) -> T {
+        self.fetch_or(flags, ordering)
+    }
+
+    /// Removes the given flags, returning the previous set.
+    pub fn fetch_remove(&self, flags: T, ordering: Ordering) -> T {
+        self.fetch_and(flags.complement(), ordering)
+    }
+
+    /// Flips the given flags, returning the previous set.
+    pub fn fetch_toggle(&self, flags: T, ordering: Ordering) -> T {
+        self.fetch_xor(flags, ordering)
+    }
+
+    /// Returns whether all the given flags are currently set.
+    pub fn contains(&self, flags: T, ordering: Ordering) -> bool {
+        self.load(ordering).contains(flags)
+    }
+}
+
+/// Formats the flags as `Name(A | B)`, used by the derived `Debug` implementations.
+#[doc(hidden)]
+pub fn fmt_flags<T: Flags>(name: &str, flags: T, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{name}(")?;
+    let mut empty = true;
+    for &(flag_name, flag) in T::FLAGS {
+        if flags.contains(flag) {
+            if !empty {
+                f.write_str(" | ")?;
+            }
+            f.write_str(flag_name)?;
+            empty = false;
+        }
+    }
+    if empty {
+        f.write_str("empty")?;
+    }
+    f.write_str(")")
+}
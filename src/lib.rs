@@ -1,9 +1,23 @@
 //! Convenient tool for atomics in Rust.
 //!
 //! # Crate features
-//! `alloc` --- enables the `Arc` type. (default)
+//! `alloc` --- enables the `Arc` type, the [`arc`] and [`boxed`] modules and
+//! [`AtomicBitVec`](bitset::AtomicBitVec). (default)
 //! `derive` --- enables the derive macros. (default)
+//! `std` --- enables the [`sync`] and [`linearizability`] modules.
+//! `log` --- logs the iteration numbers of [`try_init_model_with`] and `#[atomiq::test]`.
+//! (default)
 //! `loom` --- replaces the default implementation with the `loom` mock.
+//! `loom-checkpoint` --- enables [`ModelBuilder::checkpoint_file`], storing the progress of
+//! `loom` explorations.
+//! `shuttle` --- replaces the default implementation with the `shuttle` mock, exploring random
+//! schedules, which scales to bigger models than `loom`. `loom` is used when both are enabled.
+//! `trace` --- records every atomic operation, see the [`trace`] module.
+//! `race-check` --- detects data races on non-atomic data, see the `race_check` module. Does
+//! nothing under `loom` or `shuttle`.
+//! `stats` --- counts the operations of every atomic, see the [`stats`] module.
+//! `strict` --- panics when crate primitives are called with orderings that break them, such
+//! as `Relaxed` stores to an [`AtomicOption`](option::AtomicOption). Meant for debug builds.
 //!
 //! # Usage
 //! ```
@@ -21,25 +35,53 @@
 #![warn(missing_docs)]
 
 pub mod option;
+pub mod once;
+pub mod spin;
+pub mod fair;
+pub mod bitset;
+pub mod bitfield;
+pub mod flags;
+pub mod seqlock;
+#[cfg(feature = "std")]
+pub mod sync;
+#[cfg(feature = "trace")]
+pub mod trace;
+#[cfg(all(feature = "race-check", not(any(feature = "loom", feature = "shuttle"))))]
+pub mod race_check;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod linearizability;
+#[cfg(feature = "alloc")]
+pub mod arc;
+#[cfg(feature = "alloc")]
+pub mod boxed;
 pub mod prelude;
 mod ordering;
 mod atomic;
 mod atom;
 mod atomizable;
+mod cell;
+mod strict;
 mod try_init_model;
 mod cancellation_token;
 
 pub use atomic::Atomic;
 pub use atom::{Atom, BitAtom, IntAtom};
-pub use atomizable::{Atomizable, BitAtomizable, IntAtomizable, Atomize};
+pub use atomizable::{Atomizable, BitAtomizable, IntAtomizable, AffineAtomizable, Atomize};
+pub use flags::Flags;
 pub use ordering::{Ordering, OrderingExt};
-pub use try_init_model::try_init_model;
+pub use try_init_model::{try_init_model, try_init_model_with, LogLevel, ModelBuilder, Scheduler};
 pub use cancellation_token::*;
 
 #[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 #[cfg(feature = "alloc")]
 pub use atom::Arc;
 
 #[cfg(feature = "derive")]
-pub use atomiq_derive as derive;
\ No newline at end of file
+pub use atomiq_derive as derive;
+#[cfg(feature = "derive")]
+pub use atomiq_derive::test;
\ No newline at end of file
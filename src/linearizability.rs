@@ -0,0 +1,445 @@
+//! Linearizability checking of recorded histories (`std` crate feature).
+//!
+//! A [`Recorder`] records when each operation on a concurrent object is invoked and when it
+//! returns, from any number of threads. The resulting [`History`] is then checked against a
+//! sequential specification, a [`Spec`]: it is linearizable if the operations can be ordered so
+//! that each takes effect at some point between its invocation and its return, and the results
+//! match those of the specification applied in that order.
+//!
+//! The check is a Wing--Gong search with memoization of visited states. Histories of independent
+//! objects, such as the keys of a map, can be checked separately with
+//! [`History::check_partitioned`], which keeps the search small (P-compositionality).
+//!
+//! Specifications are provided for registers ([`Register`], also for [`AtomicOption`] as
+//! [`OptionRegister`]), counters ([`Counter`]) and compare-and-swap cells ([`Cas`]).
+//!
+//! [`AtomicOption`]: crate::option::AtomicOption
+//!
+//! # Examples
+//! ```
+//! use atomiq::prelude::*;
+//! use atomiq::linearizability::{Counter, CounterOp, Recorder};
+//! use atomiq::sync::thread;
+//! # use atomiq::try_init_model;
+//!
+//! # try_init_model(|| {
+//! let counter: Arc<Atomic<u64>> = Arc::new(Atomic::from(0));
+//! let recorder = Arc::new(Recorder::<Counter>::new());
+//!
+//! let handle = thread::spawn({
+//!     let (counter, recorder) = (counter.clone(), recorder.clone());
+//!     move || recorder.record(CounterOp::FetchAdd(1), || counter.fetch_add(1, Ordering::Relaxed))
+//! });
+//!
+//! recorder.record(CounterOp::FetchAdd(2), || counter.fetch_add(2, Ordering::Relaxed));
+//! recorder.record(CounterOp::Load, || counter.load(Ordering::Relaxed));
+//! handle.join().unwrap();
+//!
+//! recorder.history().check(&Counter(0)).unwrap();
+//! # });
+//! ```
+
+use core::fmt::{self, Debug, Display, Formatter};
+use core::hash::Hash;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::vec;
+use std::vec::Vec;
+use crate::sync::thread::{self, ThreadId};
+
+/// A sequential specification of a concurrent object.
+///
+/// The specification is the state of the object, and its operations are applied one at a time.
+pub trait Spec: Clone + Eq + Hash {
+    /// An operation on the object, with its arguments.
+    type Op: Clone + Debug;
+    /// The result of an operation.
+    type Ret: Clone + Debug + PartialEq;
+
+    /// Applies an operation to the state, returning its result.
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret;
+}
+
+/// A completed operation in a history.
+#[derive(Clone, Debug)]
+pub struct Entry<S: Spec> {
+    /// The operation.
+    pub op: S::Op,
+    /// The result of the operation.
+    pub ret: S::Ret,
+    /// The logical time at which the operation was invoked.
+    pub invoke: u64,
+    /// The logical time at which the operation returned.
+    pub response: u64,
+    /// The thread performing the operation.
+    pub thread: ThreadId,
+}
+
+impl<S: Spec> Display for Entry<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {}] {:?} {:?} -> {:?}", self.invoke, self.response, self.thread, self.op, self.ret)
+    }
+}
+
+/// Records the operations performed on a concurrent object by any number of threads.
+///
+/// Only operations that return are recorded.
+#[derive(Debug)]
+pub struct Recorder<S: Spec> {
+    clock: AtomicU64,
+    entries: Mutex<Vec<Entry<S>>>,
+}
+
+impl<S: Spec> Default for Recorder<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Spec> Recorder<S> {
+    /// Creates an empty recorder.
+    pub const fn new() -> Self {
+        Self { clock: AtomicU64::new(0), entries: Mutex::new(Vec::new()) }
+    }
+
+    /// Performs an operation with `f`, recording its invocation, its result and its return.
+    pub fn record<F>(&self, op: S::Op, f: F) -> S::Ret
+    where
+        F: FnOnce() -> S::Ret,
+    {
+        // The clock orders the invocation before `f`, and `f` before the return.
+        let invoke = self.clock.fetch_add(1, Ordering::SeqCst);
+        let ret = f();
+        let response = self.clock.fetch_add(1, Ordering::SeqCst);
+
+        let entry = Entry { op, ret: ret.clone(), invoke, response, thread: thread::current().id() };
+        self.entries.lock().unwrap_or_else(|error| error.into_inner()).push(entry);
+        ret
+    }
+
+    /// Returns the history recorded so far.
+    pub fn history(&self) -> History<S> {
+        History { entries: self.entries.lock().unwrap_or_else(|error| error.into_inner()).clone() }
+    }
+}
+
+/// A history of completed operations on a concurrent object.
+#[derive(Clone, Debug)]
+pub struct History<S: Spec> {
+    /// The operations, in no particular order.
+    pub entries: Vec<Entry<S>>,
+}
+
+impl<S: Spec> History<S> {
+    /// Checks that the history is linearizable, starting from the state `init`.
+    pub fn check(&self, init: &S) -> Result<(), Violation<S>> {
+        let entries: Vec<_> = self.entries.iter().collect();
+        check_entries(init, &entries)
+    }
+
+    /// Checks that the history is linearizable, checking the operations with different keys
+    /// separately, each starting from the state `init`.
+    ///
+    /// This is only correct when operations with different keys act on independent states, such
+    /// as different keys of a map.
+    pub fn check_partitioned<K, F>(&self, init: &S, key: F) -> Result<(), Violation<S>>
+    where
+        K: Eq + Hash,
+        F: Fn(&S::Op) -> K,
+    {
+        let mut partitions: HashMap<K, Vec<&Entry<S>>> = HashMap::new();
+        for entry in &self.entries {
+            partitions.entry(key(&entry.op)).or_default().push(entry);
+        }
+        partitions.values().try_for_each(|entries| check_entries(init, entries))
+    }
+}
+
+/// A history that is not linearizable.
+pub struct Violation<S: Spec> {
+    /// The operations that could not be linearized, ordered by invocation.
+    pub entries: Vec<Entry<S>>,
+}
+
+impl<S: Spec> Display for Violation<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "history is not linearizable:")?;
+        for entry in &self.entries {
+            writeln!(f, "  {}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Spec> Debug for Violation<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// A level of the search: the state after the operations linearized so far, and the operations
+/// that may be linearized next.
+struct Frame<S> {
+    state: S,
+    candidates: Vec<usize>,
+    next: usize,
+}
+
+/// Returns the operations that may be linearized next: those invoked before any remaining
+/// operation returned.
+fn candidates<S: Spec>(entries: &[&Entry<S>], done: &[u64]) -> Vec<usize> {
+    let remaining = || (0..entries.len()).filter(|&index| done[index / 64] & (1 << (index % 64)) == 0);
+    let first_response = remaining().map(|index| entries[index].response).min().unwrap_or(u64::MAX);
+    remaining().filter(|&index| entries[index].invoke < first_response).collect()
+}
+
+fn check_entries<S: Spec>(init: &S, entries: &[&Entry<S>]) -> Result<(), Violation<S>> {
+    let mut entries = entries.to_vec();
+    entries.sort_by_key(|entry| entry.invoke);
+
+    let mut done = vec![0u64; entries.len().div_ceil(64)];
+    let mut linearized = 0;
+    let mut order: Vec<usize> = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![Frame { state: init.clone(), candidates: candidates(&entries, &done), next: 0 }];
+
+    while linearized < entries.len() {
+        let Some(frame) = stack.last_mut() else {
+            let entries = entries.into_iter().cloned().collect();
+            return Err(Violation { entries });
+        };
+
+        // Backtracks once every candidate of the level was tried.
+        if frame.next == frame.candidates.len() {
+            stack.pop();
+            if let Some(index) = order.pop() {
+                done[index / 64] &= !(1 << (index % 64));
+                linearized -= 1;
+            }
+            continue;
+        }
+
+        let index = frame.candidates[frame.next];
+        frame.next += 1;
+
+        let mut state = frame.state.clone();
+        if state.apply(&entries[index].op) != entries[index].ret {
+            continue;
+        }
+        done[index / 64] |= 1 << (index % 64);
+        if !visited.insert((done.clone(), state.clone())) {
+            done[index / 64] &= !(1 << (index % 64));
+            continue;
+        }
+
+        order.push(index);
+        linearized += 1;
+        let candidates = candidates(&entries, &done);
+        stack.push(Frame { state, candidates, next: 0 });
+    }
+    Ok(())
+}
+
+/// An operation on a [`Register`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RegisterOp<T> {
+    /// Loads the value, returning `Some(value)`.
+    Load,
+    /// Stores a value, returning `None`.
+    Store(T),
+    /// Swaps the value, returning `Some(previous)`.
+    Swap(T),
+}
+
+/// A register holding a value, like [`Atomic::load`](crate::Atomic::load),
+/// [`Atomic::store`](crate::Atomic::store) and [`Atomic::swap`](crate::Atomic::swap).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Register<T>(pub T);
+
+impl<T: Clone + Debug + Eq + Hash> Spec for Register<T> {
+    type Op = RegisterOp<T>;
+    type Ret = Option<T>;
+
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret {
+        match op {
+            RegisterOp::Load => Some(self.0.clone()),
+            RegisterOp::Store(value) => {
+                self.0 = value.clone();
+                None
+            }
+            RegisterOp::Swap(value) => Some(core::mem::replace(&mut self.0, value.clone())),
+        }
+    }
+}
+
+/// A register holding an option, like [`AtomicOption`](crate::option::AtomicOption).
+pub type OptionRegister<T> = Register<Option<T>>;
+
+/// An operation on a [`Counter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CounterOp {
+    /// Loads the value.
+    Load,
+    /// Adds to the value, wrapping around, and returns the previous value.
+    FetchAdd(u64),
+    /// Subtracts from the value, wrapping around, and returns the previous value.
+    FetchSub(u64),
+}
+
+/// A counter, like [`Atomic::fetch_add`](crate::Atomic::fetch_add) and
+/// [`Atomic::fetch_sub`](crate::Atomic::fetch_sub) on a `u64`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Counter(pub u64);
+
+impl Spec for Counter {
+    type Op = CounterOp;
+    type Ret = u64;
+
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret {
+        let previous = self.0;
+        match *op {
+            CounterOp::Load => {}
+            CounterOp::FetchAdd(value) => self.0 = previous.wrapping_add(value),
+            CounterOp::FetchSub(value) => self.0 = previous.wrapping_sub(value),
+        }
+        previous
+    }
+}
+
+/// An operation on a [`Cas`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CasOp<T> {
+    /// Loads the value, returning `Ok(value)`.
+    Load,
+    /// Replaces the value if it equals the first one, returning `Ok(previous)` if it was replaced
+    /// and `Err(previous)` otherwise.
+    CompareExchange(T, T),
+}
+
+/// A compare-and-swap cell, like [`Atomic::compare_exchange`](crate::Atomic::compare_exchange).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cas<T>(pub T);
+
+impl<T: Clone + Debug + Eq + Hash> Spec for Cas<T> {
+    type Op = CasOp<T>;
+    type Ret = Result<T, T>;
+
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret {
+        match op {
+            CasOp::Load => Ok(self.0.clone()),
+            CasOp::CompareExchange(current, _) if *current != self.0 => Err(self.0.clone()),
+            CasOp::CompareExchange(_, new) => Ok(core::mem::replace(&mut self.0, new.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn entry<S: Spec>(op: S::Op, ret: S::Ret, invoke: u64, response: u64) -> Entry<S> {
+        Entry { op, ret, invoke, response, thread: thread::current().id() }
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    fn test_linearizability_check() {
+        // The load overlaps the store, so it may see either value.
+        let history = History::<Register<u32>> {
+            entries: vec![
+                entry(RegisterOp::Store(1), None, 0, 3),
+                entry(RegisterOp::Load, Some(0), 1, 2),
+                entry(RegisterOp::Load, Some(1), 4, 5),
+            ],
+        };
+        assert!(history.check(&Register(0)).is_ok());
+
+        // The second load starts after the store returned, so it cannot see the old value.
+        let history = History::<Register<u32>> {
+            entries: vec![
+                entry(RegisterOp::Store(1), None, 0, 1),
+                entry(RegisterOp::Load, Some(0), 2, 3),
+            ],
+        };
+        let violation = history.check(&Register(0)).unwrap_err();
+        assert_eq!(violation.entries.len(), 2);
+
+        // Two successful exchanges from the same value cannot both be linearized.
+        let history = History::<Cas<u32>> {
+            entries: vec![
+                entry(CasOp::CompareExchange(0, 1), Ok(0), 0, 2),
+                entry(CasOp::CompareExchange(0, 2), Ok(0), 1, 3),
+            ],
+        };
+        assert!(history.check(&Cas(0)).is_err());
+    }
+
+    /// Registers indexed by a key, all starting from the same value.
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct Keyed(Register<u32>);
+
+    impl Spec for Keyed {
+        type Op = (u8, RegisterOp<u32>);
+        type Ret = Option<u32>;
+
+        fn apply(&mut self, (_, op): &Self::Op) -> Self::Ret {
+            self.0.apply(op)
+        }
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    fn test_linearizability_partitioned() {
+        let history = History::<Keyed> {
+            entries: vec![
+                entry((0, RegisterOp::Store(1)), None, 0, 1),
+                entry((1, RegisterOp::Load), Some(0), 2, 3),
+                entry((0, RegisterOp::Load), Some(1), 4, 5),
+            ],
+        };
+        assert!(history.check(&Keyed(Register(0))).is_err());
+        assert!(history.check_partitioned(&Keyed(Register(0)), |(key, _)| *key).is_ok());
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    fn test_linearizability_stress() {
+        extern crate std;
+        use crate::prelude::*;
+        use crate::option::AtomicOption;
+
+        let counter: Atomic<u64> = Atomic::from(0);
+        let option: AtomicOption<u32> = AtomicOption::none();
+        let counter_recorder = Recorder::<Counter>::new();
+        let option_recorder = Recorder::<OptionRegister<u32>>::new();
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let (counter, option) = (&counter, &option);
+                let (counter_recorder, option_recorder) = (&counter_recorder, &option_recorder);
+                scope.spawn(move || {
+                    for value in 0..25 {
+                        counter_recorder.record(CounterOp::FetchAdd(1), || counter.fetch_add(1, Ordering::AcqRel));
+                        counter_recorder.record(CounterOp::Load, || counter.load(Ordering::Acquire));
+
+                        // Stores racing with `None` are not linearizable, so only one thread
+                        // stores, and only `Some`.
+                        if thread == 0 {
+                            option_recorder.record(RegisterOp::Store(Some(value)), || {
+                                option.store_some(value, Ordering::SeqCst);
+                                None
+                            });
+                        } else {
+                            option_recorder.record(RegisterOp::Load, || Some(option.load(Ordering::SeqCst)));
+                        }
+                    }
+                });
+            }
+        });
+
+        counter_recorder.history().check(&Counter(0)).unwrap();
+        option_recorder.history().check(&Register(None)).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+//! One-time initialization.
+//!
+//! See [`OnceFlag`] and [`AtomicOnceCell`] for more information.
+
+use core::convert::Infallible;
+use crate::atom::spin_loop;
+use crate::prelude::*;
+
+const UNINIT: u8 = 0;
+const INITIALIZING: u8 = 1;
+const READY: u8 = 2;
+
+/// A flag for running an initialization routine exactly once.
+///
+/// This is a `no_std` version of `std::sync::Once`. Threads that arrive while another thread is
+/// running the routine spin until it finishes, so routines should be short.
+///
+/// If the routine fails or panics, the flag is reset and the next caller runs its own routine.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::once::OnceFlag;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let flag = OnceFlag::new();
+/// let calls: Atomic<u32> = Atomic::from(0);
+///
+/// flag.call_once(|| { calls.fetch_add(1, Ordering::Relaxed); });
+/// flag.call_once(|| { calls.fetch_add(1, Ordering::Relaxed); });
+///
+/// assert!(flag.is_completed());
+/// assert_eq!(calls.load(Ordering::Relaxed), 1);
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct OnceFlag(Atomic<u8>);
+
+/// Resets the flag if the routine panics.
+struct ResetOnDrop<'a>(&'a Atomic<u8>);
+
+impl Drop for ResetOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.store(UNINIT, Ordering::Release);
+    }
+}
+
+impl OnceFlag {
+    /// Creates a new flag that has not been completed yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether a routine has completed successfully.
+    pub fn is_completed(&self) -> bool {
+        self.0.load(Ordering::Acquire) == READY
+    }
+
+    /// Runs `f` if no routine has completed yet.
+    ///
+    /// When this method returns, a routine has completed, and its effects are visible to the
+    /// current thread.
+    pub fn call_once<F>(&self, f: F)
+    where
+        F: FnOnce(),
+    {
+        let result: Result<(), Infallible> = self.try_call_once(|| {
+            f();
+            Ok(())
+        });
+        match result {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+    }
+
+    /// Runs the fallible `f` if no routine has completed yet.
+    ///
+    /// If `f` fails, the flag stays uncompleted and the error is returned.
+    pub fn try_call_once<F, E>(&self, f: F) -> Result<(), E>
+    where
+        F: FnOnce() -> Result<(), E>,
+    {
+        loop {
+            match self.0.compare_exchange_weak(UNINIT, INITIALIZING, Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => {
+                    let reset = ResetOnDrop(&self.0);
+                    let result = f();
+                    core::mem::forget(reset);
+
+                    return match result {
+                        Ok(()) => {
+                            self.0.store(READY, Ordering::Release);
+                            Ok(())
+                        }
+                        Err(err) => {
+                            self.0.store(UNINIT, Ordering::Release);
+                            Err(err)
+                        }
+                    };
+                }
+                Err(READY) => return Ok(()),
+                // Either another thread is initializing, or the weak exchange failed spuriously.
+                Err(_) => spin_loop(),
+            }
+        }
+    }
+}
+
+/// An atomic cell that can be written only once.
+///
+/// This is a lock-free version of `OnceLock<T>`, where `T` is an atomic type.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::once::AtomicOnceCell;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let cell: AtomicOnceCell<u32> = AtomicOnceCell::new();
+///
+/// assert_eq!(cell.get(), None);
+/// assert_eq!(cell.get_or_init(|| 42), 42);
+/// assert_eq!(cell.set(43), Err(43));
+/// assert_eq!(cell.get(), Some(42));
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct AtomicOnceCell<T: Atomizable> {
+    flag: OnceFlag,
+    value: Atomic<T>,
+}
+
+impl<T: Atomizable> Default for AtomicOnceCell<T> {
+    fn default() -> Self {
+        Self {
+            flag: OnceFlag::new(),
+            value: Atomic::default(),
+        }
+    }
+}
+
+impl<T: Atomizable> AtomicOnceCell<T> {
+    /// Creates a new empty cell.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value, or `None` if the cell has not been initialized yet.
+    pub fn get(&self) -> Option<T> {
+        if self.flag.is_completed() {
+            // The flag synchronizes with the store of the value.
+            Some(self.value.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+
+    /// Initializes the cell with the given value.
+    ///
+    /// If the cell is already initialized, the value is given back. If another thread is
+    /// initializing the cell, waits for it to finish first.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.flag.call_once(|| self.value.store(value.take().unwrap(), Ordering::Relaxed));
+        match value {
+            None => Ok(()),
+            Some(value) => Err(value),
+        }
+    }
+
+    /// Returns the value, initializing the cell with `f` if needed.
+    ///
+    /// If another thread is initializing the cell, waits for it to finish instead.
+    pub fn get_or_init<F>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let result: Result<T, Infallible> = self.try_get_or_init(|| Ok(f()));
+        match result {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Returns the value, initializing the cell with the fallible `f` if needed.
+    ///
+    /// If `f` fails, the cell stays uninitialized and the error is returned.
+    pub fn try_get_or_init<F, E>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+
+        self.flag.try_call_once(|| {
+            self.value.store(f()?, Ordering::Relaxed);
+            Ok(())
+        })?;
+        Ok(self.value.load(Ordering::Relaxed))
+    }
+}
+
+impl<T: Atomizable> From<T> for AtomicOnceCell<T> {
+    fn from(value: T) -> Self {
+        Self {
+            flag: OnceFlag(Atomic::from(READY)),
+            value: Atomic::from(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+    #[cfg(feature = "loom")]
+    use loom::thread;
+
+    #[test]
+    fn test_atomic_once_cell_sync() {
+        try_init_model(|| {
+            let cell: AtomicOnceCell<i32> = AtomicOnceCell::new();
+            assert_eq!(cell.get(), None);
+
+            assert_eq!(cell.try_get_or_init(|| Err("failed")), Err("failed"));
+            assert_eq!(cell.get(), None);
+
+            assert_eq!(cell.set(1), Ok(()));
+            assert_eq!(cell.set(2), Err(2));
+            assert_eq!(cell.get_or_init(|| 3), 1);
+            assert_eq!(cell.try_get_or_init(|| Err("unused")), Ok(1));
+        });
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    fn test_once_flag_reset_on_panic() {
+        extern crate std;
+
+        let flag = OnceFlag::new();
+
+        let result = std::panic::catch_unwind(|| flag.call_once(|| panic!("initializer failed")));
+        assert!(result.is_err());
+        assert!(!flag.is_completed());
+
+        flag.call_once(|| {});
+        assert!(flag.is_completed());
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_atomic_once_cell_loom_race() {
+        try_init_model(|| {
+            let cell: Arc<AtomicOnceCell<i32>> = Arc::new(AtomicOnceCell::new());
+            let calls: Arc<Atomic<u32>> = Arc::new(Atomic::from(0));
+
+            let threads: [_; 2] = core::array::from_fn(|i| thread::spawn({
+                let cell = cell.clone();
+                let calls = calls.clone();
+                move || cell.get_or_init(|| {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                    i as i32
+                })
+            }));
+
+            let [a, b] = threads.map(|thread| thread.join().unwrap());
+            assert_eq!(a, b);
+            assert_eq!(cell.get(), Some(a));
+            assert_eq!(calls.load(Ordering::Relaxed), 1);
+        });
+    }
+}
@@ -4,6 +4,7 @@
 #![doc()]
 
 use crate::prelude::*;
+use crate::strict;
 
 /// An atomic option.
 ///
@@ -12,6 +13,7 @@ use crate::prelude::*;
 /// Note that if you use [`Ordering::Relaxed`], the `is_some` flag may be set to `true`
 /// before the value is set, so this ordering should be avoided. With orderings stronger
 /// than `Relaxed`, the value is guaranteed to be set before the `is_some` flag.
+/// With the `strict` crate feature, loading or storing a value with `Relaxed` panics.
 /// 
 /// # Examples
 /// ```
@@ -73,7 +75,9 @@ impl<T: Atomizable> AtomicOption<T> {
     }
 
     /// Loads the value with the given ordering.
+    #[cfg_attr(feature = "strict", track_caller)]
     pub fn load(&self, ordering: Ordering) -> Option<T> {
+        strict::check_consume("AtomicOption::load", ordering);
         if self.is_some(ordering) {
             Some(self.value.load(ordering))
         } else {
@@ -82,9 +86,11 @@ impl<T: Atomizable> AtomicOption<T> {
     }
 
     /// Stores a value with the given ordering.
+    #[cfg_attr(feature = "strict", track_caller)]
     pub fn store(&self, value: Option<T>, ordering: Ordering) {
         match value {
             Some(value) => {
+                strict::check_publish("AtomicOption::store", ordering);
                 // First store the value, then set the flag,
                 // so that the value is never read before it's initialized.
                 self.value.store(value, ordering);
@@ -97,11 +103,13 @@ impl<T: Atomizable> AtomicOption<T> {
     }
 
     /// Stores `None` with the given ordering.
+    #[cfg_attr(feature = "strict", track_caller)]
     pub fn store_none(&self, ordering: Ordering) {
         self.store(None, ordering);
     }
 
     /// Stores `Some` with the given ordering.
+    #[cfg_attr(feature = "strict", track_caller)]
     pub fn store_some(&self, value: T, ordering: Ordering) {
         self.store(Some(value), ordering);
     }
@@ -110,6 +118,7 @@ impl<T: Atomizable> AtomicOption<T> {
     /// 
     /// # Panics
     /// Panics if the option is `None`.
+    #[cfg_attr(feature = "strict", track_caller)]
     pub fn unwrap(&self, ordering: Ordering) -> T {
         self.load(ordering).unwrap()
     }
@@ -204,11 +213,14 @@ mod tests {
 
     #[test]
     fn test_atomic_option_loom_sync() {
+        // Relaxed loads and stores are rejected in strict mode.
+        let ordering = if cfg!(feature = "strict") { Ordering::AcqRel } else { Ordering::Relaxed };
+
         let i = RealArc::new(AtomicU32::new(0));
         loom::model(move || {
             let i = i.fetch_add(1, Ordering::Relaxed) + 1;
             info!("Testing iteration {i}...");
-            test_atomic_option_sync(Ordering::Relaxed);
+            test_atomic_option_sync(ordering);
         });
     }
 
@@ -5,8 +5,9 @@ pub use crate::{Ordering, OrderingExt};
 
 pub use crate::atomic::Atomic;
 pub use crate::atom::*;
-pub use crate::atomizable::{Atomizable, BitAtomizable, IntAtomizable, Atomize};
+pub use crate::atomizable::{Atomizable, BitAtomizable, IntAtomizable, AffineAtomizable, Atomize};
 pub use crate::cancellation_token::*;
+pub use crate::flags::Flags;
 
 #[cfg(feature = "derive")]
-pub use crate::derive::*;
\ No newline at end of file
+pub use crate::derive::{Atomizable, BitAtomizable, IntAtomizable, AffineAtomizable, AtomicFlags, AtomicStruct};
\ No newline at end of file
@@ -0,0 +1,746 @@
+//! Happens-before data race detection (`race-check` crate feature).
+//!
+//! With the `race-check` feature, every thread keeps a vector clock, and every [`Atomic`] keeps
+//! the clock released by its last stores, following the orderings passed to its operations.
+//! [`TrackedCell`] holds non-atomic data and checks on every access that the previous
+//! conflicting accesses happen before it, panicking with a report otherwise.
+//!
+//! The report shows both accesses, and the atomic operations that would have ordered them with
+//! stronger orderings, such as a `Relaxed` load that needs to be `Acquire` to see a value
+//! published by a `Release` store.
+//!
+//! Only atomics, fences, and threads spawned with [`sync::thread::spawn`] and joined are tracked.
+//! Accesses ordered by other means, such as a standard library `Mutex`, are reported as races.
+//! Under `loom`, which detects races itself, and under `shuttle`, whose threads share one OS
+//! thread, the `race-check` feature does nothing: this module is not compiled and
+//! [`TrackedCell`](crate::sync::cell::TrackedCell) is the backend's `UnsafeCell`.
+//!
+//! [`sync::thread::spawn`]: crate::sync::thread::spawn
+//!
+//! # Examples
+//! ```should_panic
+//! use atomiq::prelude::*;
+//! use atomiq::sync::cell::TrackedCell;
+//! use atomiq::sync::thread;
+//!
+//! struct Data(TrackedCell<u32>);
+//! // Safety: accesses to the cell are ordered by `ready`.
+//! unsafe impl Sync for Data {}
+//!
+//! let data = Arc::new(Data(TrackedCell::new(0)));
+//! let ready = Arc::new(Atomic::from(false));
+//!
+//! thread::spawn({
+//!     let (data, ready) = (data.clone(), ready.clone());
+//!     move || {
+//!         data.0.with_mut(|data| unsafe { *data = 42 });
+//!         // Should be `Release`.
+//!         ready.store(true, Ordering::Relaxed);
+//!     }
+//! });
+//!
+//! while !ready.load(Ordering::Acquire) {}
+//! // Panics: the write of the other thread does not happen before this read.
+//! data.0.with(|data| unsafe { *data });
+//! ```
+
+use core::cell::{RefCell, UnsafeCell};
+use core::fmt::{self, Debug, Formatter, Write};
+use core::panic::Location;
+use std::collections::VecDeque;
+use std::string::String;
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::AtomicUsize;
+use std::vec::Vec;
+use crate::prelude::*;
+
+/// How many weakly ordered loads each thread remembers for its reports.
+const WEAK_LOADS: usize = 64;
+
+fn acquires(ordering: Ordering) -> bool {
+    matches!(ordering, Ordering::Acquire | Ordering::AcqRel | Ordering::SeqCst)
+}
+
+fn releases(ordering: Ordering) -> bool {
+    matches!(ordering, Ordering::Release | Ordering::AcqRel | Ordering::SeqCst)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // The states are updated without panicking in between, so they are never left inconsistent.
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// A vector clock, indexed by thread.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct VectorClock(Vec<u64>);
+
+impl VectorClock {
+    fn get(&self, thread: usize) -> u64 {
+        self.0.get(thread).copied().unwrap_or(0)
+    }
+
+    fn set(&mut self, thread: usize, time: u64) {
+        if self.0.len() <= thread {
+            self.0.resize(thread + 1, 0);
+        }
+        self.0[thread] = time;
+    }
+
+    fn join(&mut self, other: &VectorClock) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (time, other) in self.0.iter_mut().zip(&other.0) {
+            *time = (*time).max(*other);
+        }
+    }
+
+    fn covers(&self, other: &VectorClock) -> bool {
+        other.0.iter().enumerate().all(|(thread, time)| *time <= self.get(thread))
+    }
+}
+
+/// A store to an atomic, remembered for reports.
+#[derive(Clone, Copy, Debug)]
+struct Store {
+    ordering: Ordering,
+    caller: &'static Location<'static>,
+}
+
+/// A load that would have synchronized with more accesses with stronger orderings.
+#[derive(Clone, Debug)]
+struct WeakLoad {
+    ordering: Ordering,
+    caller: &'static Location<'static>,
+    /// The clock the load would have acquired if all orderings were `SeqCst`.
+    strong: VectorClock,
+    store: Option<Store>,
+}
+
+/// The race detection state of a thread.
+struct ThreadState {
+    index: usize,
+    clock: VectorClock,
+    /// The clock the thread would have if all orderings were `SeqCst`.
+    strong: VectorClock,
+    /// The clocks read by non-acquiring loads, acquired by the next acquire fence.
+    fence_acquire: VectorClock,
+    /// The clock at the last release fence, released by the following stores.
+    fence_release: Option<VectorClock>,
+    weak_loads: VecDeque<WeakLoad>,
+}
+
+impl ThreadState {
+    fn new() -> Self {
+        static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+        let index = THREADS.fetch_add(1, Ordering::Relaxed);
+        let mut clock = VectorClock::default();
+        clock.set(index, 1);
+        Self {
+            index,
+            strong: clock.clone(),
+            clock,
+            fence_acquire: VectorClock::default(),
+            fence_release: None,
+            weak_loads: VecDeque::new(),
+        }
+    }
+
+    fn time(&self) -> u64 {
+        self.clock.get(self.index)
+    }
+
+    /// Starts a new epoch, so that the following accesses are not covered by earlier releases.
+    fn tick(&mut self) {
+        let time = self.time() + 1;
+        self.clock.set(self.index, time);
+        self.strong.set(self.index, time);
+    }
+
+    fn acquire(&mut self, clock: &VectorClock) {
+        self.clock.join(clock);
+        self.strong.join(clock);
+    }
+
+    fn load(&mut self, location: &mut LocationState, ordering: Ordering, caller: &'static Location<'static>) {
+        if acquires(ordering) {
+            self.clock.join(&location.clock);
+        } else {
+            self.fence_acquire.join(&location.clock);
+        }
+        if !self.clock.covers(&location.strong) {
+            if self.weak_loads.len() == WEAK_LOADS {
+                self.weak_loads.pop_front();
+            }
+            self.weak_loads.push_back(WeakLoad {
+                ordering,
+                caller,
+                strong: location.strong.clone(),
+                store: location.store,
+            });
+        }
+        self.strong.join(&location.strong);
+    }
+
+    fn released(&self, ordering: Ordering) -> VectorClock {
+        if releases(ordering) {
+            self.clock.clone()
+        } else {
+            self.fence_release.clone().unwrap_or_default()
+        }
+    }
+}
+
+std::thread_local! {
+    static THREAD: RefCell<ThreadState> = RefCell::new(ThreadState::new());
+}
+
+fn with_thread<R>(f: impl FnOnce(&mut ThreadState) -> R) -> R {
+    THREAD.with(|thread| f(&mut thread.borrow_mut()))
+}
+
+/// The race detection state of an atomic.
+#[derive(Debug, Default)]
+struct LocationState {
+    /// The clock released by the stores the last load would read from.
+    clock: VectorClock,
+    /// The clock that would be released if all orderings were `SeqCst`.
+    strong: VectorClock,
+    store: Option<Store>,
+}
+
+/// A provider tracking the clocks released and acquired through the provider it wraps.
+#[doc(hidden)]
+pub struct Raced<P> {
+    inner: P,
+    state: Mutex<LocationState>,
+}
+
+impl<P> Raced<P> {
+    /// Performs a load with `f`, while no other operation on the atomic is tracked.
+    #[track_caller]
+    pub(crate) fn track_load<R>(&self, ordering: Ordering, f: impl FnOnce(&P) -> R) -> R {
+        let caller = Location::caller();
+        let mut state = lock(&self.state);
+        let value = f(&self.inner);
+        with_thread(|thread| thread.load(&mut state, ordering, caller));
+        value
+    }
+
+    /// Performs a store with `f`, while no other operation on the atomic is tracked.
+    #[track_caller]
+    pub(crate) fn track_store<R>(&self, ordering: Ordering, f: impl FnOnce(&P) -> R) -> R {
+        let caller = Location::caller();
+        let mut state = lock(&self.state);
+        let value = f(&self.inner);
+        with_thread(|thread| {
+            state.clock = thread.released(ordering);
+            state.strong = thread.strong.clone();
+            state.store = Some(Store { ordering, caller });
+            thread.tick();
+        });
+        value
+    }
+
+    /// Performs a read-modify-write with `f`, while no other operation on the atomic is tracked.
+    ///
+    /// The operation continues the release sequence of the previous stores.
+    #[track_caller]
+    pub(crate) fn track_update<R>(&self, ordering: Ordering, f: impl FnOnce(&P) -> R) -> R {
+        let caller = Location::caller();
+        let mut state = lock(&self.state);
+        let value = f(&self.inner);
+        with_thread(|thread| {
+            thread.load(&mut state, ordering, caller);
+            let released = thread.released(ordering);
+            state.clock.join(&released);
+            state.strong.join(&thread.strong.clone());
+            state.store = Some(Store { ordering, caller });
+            thread.tick();
+        });
+        value
+    }
+
+    /// Performs an exchange with `f`, which is an update if it succeeds and a load otherwise.
+    #[track_caller]
+    pub(crate) fn track_exchange<A>(&self, success: Ordering, failure: Ordering, f: impl FnOnce(&P) -> Result<A, A>) -> Result<A, A> {
+        let caller = Location::caller();
+        let mut state = lock(&self.state);
+        let result = f(&self.inner);
+        with_thread(|thread| match result {
+            Ok(_) => {
+                thread.load(&mut state, success, caller);
+                let released = thread.released(success);
+                state.clock.join(&released);
+                state.strong.join(&thread.strong.clone());
+                state.store = Some(Store { ordering: success, caller });
+                thread.tick();
+            }
+            Err(_) => thread.load(&mut state, failure, caller),
+        });
+        result
+    }
+}
+
+impl<P> Raced<P> {
+    pub(crate) fn new(inner: P) -> Self {
+        Self { inner, state: Mutex::default() }
+    }
+}
+
+impl<P: Debug> Debug for Raced<P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<P: Default> Default for Raced<P> {
+    fn default() -> Self {
+        Self::new(P::default())
+    }
+}
+
+
+macro_rules! raced_impl {
+    ($atom:ty, $provider:ty $(, <$generic:ident>)?) => {
+        impl$(<$generic>)? From<$atom> for Raced<$provider> {
+            fn from(value: $atom) -> Self {
+                Raced::new(<$provider>::new(value))
+            }
+        }
+
+        impl$(<$generic>)? Raced<$provider> {
+            #[track_caller]
+            pub(crate) fn load(&self, ordering: Ordering) -> $atom {
+                self.track_load(ordering, |inner| inner.load(ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn store(&self, value: $atom, ordering: Ordering) {
+                self.track_store(ordering, |inner| inner.store(value, ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn swap(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.swap(value, ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn compare_exchange(
+                &self,
+                current: $atom,
+                new: $atom,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$atom, $atom> {
+                self.track_exchange(success, failure, |inner| inner.compare_exchange(current, new, success, failure))
+            }
+
+            #[track_caller]
+            pub(crate) fn compare_exchange_weak(
+                &self,
+                current: $atom,
+                new: $atom,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$atom, $atom> {
+                self.track_exchange(success, failure, |inner| inner.compare_exchange_weak(current, new, success, failure))
+            }
+
+            /// Updates the value like `fetch_update` of the standard atomics, tracking each load
+            /// and exchange, so that `f` runs while other operations on the atomic are tracked.
+            #[track_caller]
+            pub(crate) fn fetch_update<F>(
+                &self,
+                set_ordering: Ordering,
+                fetch_ordering: Ordering,
+                mut f: F,
+            ) -> Result<$atom, $atom>
+            where
+                F: FnMut($atom) -> Option<$atom>,
+            {
+                let mut previous = self.load(fetch_ordering);
+                while let Some(next) = f(previous) {
+                    match self.compare_exchange_weak(previous, next, set_ordering, fetch_ordering) {
+                        Ok(value) => return Ok(value),
+                        Err(value) => previous = value,
+                    }
+                }
+                Err(previous)
+            }
+        }
+    };
+    ($atom:ty, $provider:ty, bit) => {
+        impl Raced<$provider> {
+            #[track_caller]
+            pub(crate) fn fetch_and(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.fetch_and(value, ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn fetch_nand(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.fetch_nand(value, ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn fetch_or(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.fetch_or(value, ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn fetch_xor(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.fetch_xor(value, ordering))
+            }
+        }
+    };
+    ($atom:ty, $provider:ty, int) => {
+        impl Raced<$provider> {
+            #[track_caller]
+            pub(crate) fn fetch_add(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.fetch_add(value, ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn fetch_sub(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.fetch_sub(value, ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn fetch_min(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.fetch_min(value, ordering))
+            }
+
+            #[track_caller]
+            pub(crate) fn fetch_max(&self, value: $atom, ordering: Ordering) -> $atom {
+                self.track_update(ordering, |inner| inner.fetch_max(value, ordering))
+            }
+        }
+    };
+}
+
+pub(crate) use raced_impl;
+
+/// An atomic fence, also ordering the tracked accesses.
+#[track_caller]
+pub fn fence(ordering: Ordering) {
+    core::sync::atomic::fence(ordering);
+    with_thread(|thread| {
+        if acquires(ordering) {
+            let clock = core::mem::take(&mut thread.fence_acquire);
+            thread.clock.join(&clock);
+        }
+        if releases(ordering) {
+            thread.fence_release = Some(thread.clock.clone());
+            thread.tick();
+        }
+    });
+}
+
+/// An access to a [`TrackedCell`].
+#[derive(Clone, Copy, Debug)]
+struct Access {
+    write: bool,
+    thread: usize,
+    time: u64,
+    caller: &'static Location<'static>,
+}
+
+impl Access {
+    fn describe(&self) -> String {
+        let kind = if self.write { "write" } else { "read" };
+        std::format!("{} at {} on thread #{}", kind, self.caller, self.thread)
+    }
+}
+
+#[derive(Debug, Default)]
+struct CellState {
+    write: Option<Access>,
+    /// The last read of each thread since the last write.
+    reads: Vec<Access>,
+}
+
+/// An `UnsafeCell` checking that conflicting accesses are ordered by happens-before.
+///
+/// Accesses go through [`with`](Self::with) and [`with_mut`](Self::with_mut), like
+/// `loom::cell::UnsafeCell`. A write conflicts with any other access, and a read with any write.
+///
+/// Like `UnsafeCell`, the cell is not `Sync`, so it is shared through a type ordering its
+/// accesses, which implements `Sync` itself.
+///
+/// # Panics
+/// Accessing the cell panics if a conflicting access does not happen before it.
+pub struct TrackedCell<T: ?Sized> {
+    state: Mutex<CellState>,
+    value: UnsafeCell<T>,
+}
+
+impl<T: Default> Default for TrackedCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> Debug for TrackedCell<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrackedCell").finish_non_exhaustive()
+    }
+}
+
+impl<T> TrackedCell<T> {
+    /// Creates a new cell containing the value.
+    pub const fn new(data: T) -> Self {
+        Self { state: Mutex::new(CellState { write: None, reads: Vec::new() }), value: UnsafeCell::new(data) }
+    }
+
+    /// Unwraps the value.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> TrackedCell<T> {
+    /// Calls `f` with an immutable pointer to the value, checking that the last write happens
+    /// before.
+    #[track_caller]
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(*const T) -> R,
+    {
+        self.access(false, Location::caller());
+        f(self.value.get())
+    }
+
+    /// Calls `f` with a mutable pointer to the value, checking that all other accesses happen
+    /// before.
+    #[track_caller]
+    pub fn with_mut<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(*mut T) -> R,
+    {
+        self.access(true, Location::caller());
+        f(self.value.get())
+    }
+
+    fn access(&self, write: bool, caller: &'static Location<'static>) {
+        let report = with_thread(|thread| {
+            let access = Access { write, thread: thread.index, time: thread.time(), caller };
+            let mut state = lock(&self.state);
+            let ordered = |previous: &Access| previous.thread == thread.index || previous.time <= thread.clock.get(previous.thread);
+
+            let conflict = state
+                .write
+                .iter()
+                .chain(state.reads.iter().filter(|_| write))
+                .find(|previous| !ordered(previous))
+                .copied();
+            if let Some(previous) = conflict {
+                return Some(report(thread, &previous, &access));
+            }
+
+            if write {
+                state.write = Some(access);
+                state.reads.clear();
+            } else {
+                state.reads.retain(|read| read.thread != thread.index);
+                state.reads.push(access);
+            }
+            None
+        });
+        if let Some(report) = report {
+            panic!("{}", report);
+        }
+    }
+}
+
+/// Describes a race between `previous` and `access`, and the orderings that would prevent it.
+fn report(thread: &ThreadState, previous: &Access, access: &Access) -> String {
+    let mut report = std::format!(
+        "data race on TrackedCell: {} does not happen before {}.",
+        previous.describe(),
+        access.describe(),
+    );
+
+    let mut weak = thread
+        .weak_loads
+        .iter()
+        .filter(|load| previous.time <= load.strong.get(previous.thread))
+        .peekable();
+    if weak.peek().is_none() {
+        report.push_str("\nNo atomic operation orders them.");
+    }
+    for load in weak {
+        if !acquires(load.ordering) {
+            let _ = write!(report, "\nLoad at {} uses {:?}, it needs Acquire.", load.caller, load.ordering);
+        }
+        if let Some(store) = load.store.filter(|store| !releases(store.ordering)) {
+            let _ = write!(report, "\nStore at {} uses {:?}, it needs Release.", store.caller, store.ordering);
+        }
+    }
+    report
+}
+
+/// Threads ordering the tracked accesses of the spawning and joining threads.
+pub mod thread {
+    use super::{with_thread, VectorClock};
+
+    /// Spawns a thread, like `std::thread::spawn`, after which the accesses of the current thread
+    /// happen before those of the new thread.
+    pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let parent = with_thread(|thread| {
+            let clock = thread.clock.clone();
+            thread.tick();
+            clock
+        });
+        JoinHandle(std::thread::spawn(move || {
+            with_thread(|thread| thread.acquire(&parent));
+            let value = f();
+            let clock = with_thread(|thread| thread.clock.clone());
+            (value, clock)
+        }))
+    }
+
+    /// A handle to a thread spawned with [`spawn`].
+    #[derive(Debug)]
+    pub struct JoinHandle<T>(std::thread::JoinHandle<(T, VectorClock)>);
+
+    impl<T> JoinHandle<T> {
+        /// Waits for the thread to finish, after which its accesses happen before those of the
+        /// current thread.
+        pub fn join(self) -> std::thread::Result<T> {
+            let (value, clock) = self.0.join()?;
+            with_thread(|thread| thread.acquire(&clock));
+            Ok(value)
+        }
+
+        /// Returns the handle of the thread.
+        pub fn thread(&self) -> &std::thread::Thread {
+            self.0.thread()
+        }
+
+        /// Returns whether the thread has finished.
+        pub fn is_finished(&self) -> bool {
+            self.0.is_finished()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::option::AtomicOption;
+
+    /// Data shared between the threads, of which the accesses are ordered by the tests.
+    struct Shared(TrackedCell<u32>);
+
+    // Safety: the accesses to the cell are checked.
+    unsafe impl Sync for Shared {}
+
+    impl core::ops::Deref for Shared {
+        type Target = TrackedCell<u32>;
+
+        fn deref(&self) -> &TrackedCell<u32> {
+            &self.0
+        }
+    }
+
+    fn publish(store: Ordering, load: Ordering) -> u32 {
+        let data = Arc::new(Shared(TrackedCell::new(0)));
+        let ready = Arc::new(Atomic::from(false));
+
+        let writer = thread::spawn({
+            let (data, ready) = (data.clone(), ready.clone());
+            move || {
+                data.with_mut(|data| unsafe { *data = 42 });
+                ready.store(true, store);
+            }
+        });
+
+        while !ready.load(load) {
+            core::hint::spin_loop();
+        }
+        let value = data.with(|data| unsafe { *data });
+        writer.join().unwrap();
+        value
+    }
+
+    #[test]
+    fn test_race_check_release_acquire() {
+        assert_eq!(publish(Ordering::Release, Ordering::Acquire), 42);
+    }
+
+    #[test]
+    #[should_panic(expected = "uses Relaxed, it needs Acquire")]
+    fn test_race_check_relaxed_load() {
+        publish(Ordering::Release, Ordering::Relaxed);
+    }
+
+    #[test]
+    #[should_panic(expected = "uses Relaxed, it needs Release")]
+    fn test_race_check_relaxed_store() {
+        publish(Ordering::Relaxed, Ordering::Acquire);
+    }
+
+    /// Publishes data through an [`AtomicOption`], storing with `Release` and loading with the
+    /// given ordering.
+    fn publish_option(load: Ordering) -> u32 {
+        let data = Arc::new(Shared(TrackedCell::new(0)));
+        let option: Arc<AtomicOption<u32>> = Arc::new(AtomicOption::none());
+
+        let writer = thread::spawn({
+            let (data, option) = (data.clone(), option.clone());
+            move || {
+                data.with_mut(|data| unsafe { *data = 42 });
+                option.store_some(1, Ordering::Release);
+            }
+        });
+
+        while option.load(load).is_none() {
+            core::hint::spin_loop();
+        }
+        let value = data.with(|data| unsafe { *data });
+        writer.join().unwrap();
+        value
+    }
+
+    #[test]
+    fn test_race_check_option_acquire() {
+        assert_eq!(publish_option(Ordering::Acquire), 42);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "strict"), should_panic(expected = "uses Relaxed, it needs Acquire"))]
+    #[cfg_attr(feature = "strict", should_panic(expected = "`AtomicOption::load` called with `Relaxed`"))]
+    fn test_race_check_option_relaxed() {
+        publish_option(Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_race_check_fences() {
+        let data = Arc::new(Shared(TrackedCell::new(0)));
+        let ready = Arc::new(Atomic::from(false));
+
+        let writer = thread::spawn({
+            let (data, ready) = (data.clone(), ready.clone());
+            move || {
+                data.with_mut(|data| unsafe { *data = 42 });
+                fence(Ordering::Release);
+                ready.store(true, Ordering::Relaxed);
+            }
+        });
+
+        while !ready.load(Ordering::Relaxed) {
+            core::hint::spin_loop();
+        }
+        fence(Ordering::Acquire);
+        assert_eq!(data.with(|data| unsafe { *data }), 42);
+
+        writer.join().unwrap();
+        data.with_mut(|data| unsafe { *data = 0 });
+    }
+}
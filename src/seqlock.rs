@@ -0,0 +1,143 @@
+//! Sequence locks for consistent reads of several atomics.
+//!
+//! See [`SeqLock`] for more information.
+
+use crate::atom::{fence, spin_loop};
+use crate::prelude::*;
+
+/// A sequence lock, letting readers see the effects of each write section as a whole.
+///
+/// The lock guards a group of atomics that are written together. Writers take turns, while
+/// readers never block writers: a read section is retried until no write section overlapped it.
+/// All accesses to the guarded data must still be atomic, as read sections may observe partial
+/// writes before being retried.
+///
+/// Used by `#[derive(AtomicStruct)]` with `#[atomiq(consistent)]`.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::seqlock::SeqLock;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let lock = SeqLock::new();
+/// let (low, high): (Atomic<u32>, Atomic<u32>) = (Atomic::from(0), Atomic::from(0));
+///
+/// lock.write(|| {
+///     low.store(1, Ordering::Relaxed);
+///     high.store(1, Ordering::Relaxed);
+/// });
+///
+/// let (a, b) = lock.read(|| (low.load(Ordering::Relaxed), high.load(Ordering::Relaxed)));
+/// assert_eq!((a, b), (1, 1));
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct SeqLock {
+    /// Odd while a write section is running, incremented by two for every completed write.
+    sequence: Atomic<usize>,
+}
+
+/// Ends the write section when dropped, even if the writer panics.
+struct WriteGuard<'a> {
+    lock: &'a SeqLock,
+    sequence: usize,
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        self.lock.sequence.store(self.sequence.wrapping_add(2), Ordering::Release);
+    }
+}
+
+impl SeqLock {
+    /// Creates a new lock.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` as a write section, waiting for other writers to finish first.
+    pub fn write<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.begin_write();
+        f()
+    }
+
+    /// Runs `f` as a read section, retrying it until no write section overlapped it.
+    ///
+    /// `f` may be called several times, and only the result of the last call is returned.
+    pub fn read<R>(&self, mut f: impl FnMut() -> R) -> R {
+        loop {
+            let sequence = self.sequence.load(Ordering::Acquire);
+            if sequence & 1 == 0 {
+                let result = f();
+                // Orders the reads of `f` before checking the sequence again.
+                fence(Ordering::Acquire);
+                if self.sequence.load(Ordering::Relaxed) == sequence {
+                    return result;
+                }
+            }
+            spin_loop();
+        }
+    }
+
+    fn begin_write(&self) -> WriteGuard<'_> {
+        loop {
+            let sequence = self.sequence.load(Ordering::Relaxed);
+            if sequence & 1 == 0
+                && self
+                    .sequence
+                    .compare_exchange_weak(sequence, sequence.wrapping_add(1), Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                // Orders the odd sequence before the writes of the section.
+                fence(Ordering::Release);
+                return WriteGuard { lock: self, sequence };
+            }
+            spin_loop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+    #[cfg(feature = "loom")]
+    use loom::thread;
+
+    #[test]
+    fn test_seqlock_sync() {
+        try_init_model(|| {
+            let lock = SeqLock::new();
+            let value: Atomic<u32> = Atomic::from(0);
+
+            assert_eq!(lock.write(|| value.fetch_add(2, Ordering::Relaxed)), 0);
+            assert_eq!(lock.read(|| value.load(Ordering::Relaxed)), 2);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_seqlock_loom_consistent_read() {
+        try_init_model(|| {
+            let lock = Arc::new(SeqLock::new());
+            let pair: Arc<[Atomic<u32>; 2]> = Arc::new([Atomic::from(0), Atomic::from(0)]);
+
+            let writer = thread::spawn({
+                let lock = lock.clone();
+                let pair = pair.clone();
+                move || lock.write(|| {
+                    pair[0].store(1, Ordering::Relaxed);
+                    pair[1].store(1, Ordering::Relaxed);
+                })
+            });
+
+            let (a, b) = lock.read(|| (pair[0].load(Ordering::Relaxed), pair[1].load(Ordering::Relaxed)));
+            assert_eq!(a, b);
+
+            writer.join().unwrap();
+        });
+    }
+}
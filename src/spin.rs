@@ -0,0 +1,718 @@
+//! Spin locks.
+//!
+//! See [`SpinMutex`] and [`SpinRwLock`] for more information.
+
+use core::fmt::{self, Debug, Formatter};
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use crate::atom::spin_loop;
+use crate::cell::{ConstPtr, MutPtr, UnsafeCell};
+use crate::prelude::*;
+
+const SPIN_LIMIT: u32 = 6;
+
+/// Exponential backoff for spin loops.
+///
+/// Each call to [`spin`](Self::spin) waits twice as long as the previous one, up to a limit,
+/// which reduces the traffic on a contended cache line.
+///
+/// # Examples
+/// ```
+/// use atomiq::prelude::*;
+/// use atomiq::spin::Backoff;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let ready: Atomic<bool> = Atomic::from(true);
+/// let mut backoff = Backoff::new();
+///
+/// while !ready.load(Ordering::Acquire) {
+///     backoff.spin();
+/// }
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    /// Creates a new backoff.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spins for a while, twice as long as the previous time, up to a limit.
+    pub fn spin(&mut self) {
+        // Under the model, every spin is a context switch, so there is no point in repeating it.
+        #[cfg(any(feature = "loom", feature = "shuttle"))]
+        spin_loop();
+        #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+        for _ in 0..1 << self.step {
+            spin_loop();
+        }
+
+        if self.step < SPIN_LIMIT {
+            self.step += 1;
+        }
+    }
+
+    /// Resets the backoff to the shortest wait.
+    pub fn reset(&mut self) {
+        self.step = 0;
+    }
+}
+
+/// A mutual exclusion lock that spins while waiting.
+///
+/// This is a `no_std` version of `Mutex<T>`. Since waiting threads burn CPU time instead of
+/// sleeping, it is only suitable for short critical sections.
+///
+/// # Examples
+/// ```
+/// use atomiq::spin::SpinMutex;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let mutex = SpinMutex::new(0);
+///
+/// *mutex.lock() += 1;
+///
+/// let guard = mutex.lock();
+/// assert_eq!(*guard, 1);
+/// assert!(mutex.try_lock().is_none());
+/// # });
+/// ```
+pub struct SpinMutex<T> {
+    locked: Atomic<bool>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinMutex<T> {}
+
+impl<T> SpinMutex<T> {
+    /// Creates a new unlocked mutex.
+    pub fn new(value: T) -> Self {
+        Self {
+            locked: Atomic::from(false),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns whether the mutex is currently locked.
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Tries to lock the mutex without waiting.
+    pub fn try_lock(&self) -> Option<SpinMutexGuard<'_, T>> {
+        self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| SpinMutexGuard {
+                mutex: self,
+                data: ManuallyDrop::new(self.data.get_mut()),
+            })
+    }
+
+    /// Locks the mutex, spinning until it is available.
+    pub fn lock(&self) -> SpinMutexGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            // Wait without writing, so that the cache line is not bounced between cores.
+            while self.is_locked() {
+                spin_loop();
+            }
+        }
+    }
+
+    /// Locks the mutex, spinning with [`Backoff`] until it is available.
+    ///
+    /// This scales better than [`lock`](Self::lock) when many threads contend for the mutex,
+    /// at the cost of a higher latency when it gets unlocked.
+    pub fn lock_with_backoff(&self) -> SpinMutexGuard<'_, T> {
+        let mut backoff = Backoff::new();
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            while self.is_locked() {
+                backoff.spin();
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// This is safe because the mutable reference guarantees that no other thread accesses it.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.with_mut(|ptr| unsafe { &mut *ptr })
+    }
+
+    /// Consumes the mutex, returning the contained value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: Default> Default for SpinMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for SpinMutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Debug> Debug for SpinMutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SpinMutex");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+/// A guard that unlocks the [`SpinMutex`] when dropped.
+pub struct SpinMutexGuard<'a, T> {
+    mutex: &'a SpinMutex<T>,
+    data: ManuallyDrop<MutPtr<T>>,
+}
+
+unsafe impl<T: Sync> Sync for SpinMutexGuard<'_, T> {}
+
+impl<T> Deref for SpinMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { MutPtr::deref(&self.data) }
+    }
+}
+
+impl<T> DerefMut for SpinMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { MutPtr::deref(&self.data) }
+    }
+}
+
+impl<T> Drop for SpinMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // The access has to end before the next owner starts its own.
+        unsafe { ManuallyDrop::drop(&mut self.data) };
+        self.mutex.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<T: Debug> Debug for SpinMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+const WRITER: usize = 1;
+const UPGRADABLE: usize = 2;
+const PENDING: usize = 4;
+const READER: usize = 8;
+
+/// A reader-writer lock that spins while waiting.
+///
+/// This is a `no_std` version of `RwLock<T>`. Besides shared read and exclusive write access,
+/// it supports a single upgradable read, which coexists with plain readers and can later be
+/// turned into a write without letting another writer in between.
+///
+/// The lock prefers writers: once a writer is waiting, new readers wait until it is done, so
+/// a steady stream of readers cannot starve writers.
+///
+/// # Examples
+/// ```
+/// use atomiq::spin::SpinRwLock;
+/// # use atomiq::try_init_model;
+///
+/// # try_init_model(|| {
+/// let lock = SpinRwLock::new(1);
+///
+/// {
+///     let a = lock.read();
+///     let b = lock.read();
+///     assert_eq!(*a + *b, 2);
+///     assert!(lock.try_write().is_none());
+/// }
+///
+/// let upgradable = lock.upgradable_read();
+/// if *upgradable == 1 {
+///     *upgradable.upgrade() += 1;
+/// }
+///
+/// assert_eq!(*lock.read(), 2);
+/// # });
+/// ```
+pub struct SpinRwLock<T> {
+    state: Atomic<usize>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send + Sync> Sync for SpinRwLock<T> {}
+
+impl<T> SpinRwLock<T> {
+    /// Creates a new unlocked lock.
+    pub fn new(value: T) -> Self {
+        Self {
+            state: Atomic::from(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    fn read_guard(&self) -> SpinRwLockReadGuard<'_, T> {
+        SpinRwLockReadGuard {
+            lock: self,
+            data: ManuallyDrop::new(self.data.get()),
+        }
+    }
+
+    fn upgradable_guard(&self) -> SpinRwLockUpgradableGuard<'_, T> {
+        SpinRwLockUpgradableGuard {
+            lock: self,
+            data: ManuallyDrop::new(self.data.get()),
+        }
+    }
+
+    fn write_guard(&self) -> SpinRwLockWriteGuard<'_, T> {
+        SpinRwLockWriteGuard {
+            lock: self,
+            data: ManuallyDrop::new(self.data.get_mut()),
+        }
+    }
+
+    /// Tries to take the writer bit, given the bits the caller already holds.
+    fn try_acquire_writer(&self, held: usize) -> bool {
+        self.state
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |state| {
+                (state & !PENDING == held).then_some(WRITER)
+            })
+            .is_ok()
+    }
+
+    /// Takes the writer bit, given the bits the caller already holds.
+    fn acquire_writer(&self, held: usize) {
+        let mut backoff = Backoff::new();
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & !PENDING == held {
+                if self.state.compare_exchange_weak(state, WRITER, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                    return;
+                }
+            } else if state & PENDING == 0 {
+                // Keep new readers out until the writer gets in.
+                self.state.fetch_or(PENDING, Ordering::Relaxed);
+            }
+            backoff.spin();
+        }
+    }
+
+    /// Tries to lock for reading without waiting.
+    ///
+    /// Fails if a writer holds the lock or is waiting for it.
+    pub fn try_read(&self) -> Option<SpinRwLockReadGuard<'_, T>> {
+        let state = self.state.fetch_add(READER, Ordering::Acquire);
+        if state & (WRITER | PENDING) != 0 {
+            self.state.fetch_sub(READER, Ordering::Release);
+            None
+        } else {
+            Some(self.read_guard())
+        }
+    }
+
+    /// Locks for reading, spinning until no writer holds the lock or is waiting for it.
+    pub fn read(&self) -> SpinRwLockReadGuard<'_, T> {
+        let mut backoff = Backoff::new();
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            while self.state.load(Ordering::Relaxed) & (WRITER | PENDING) != 0 {
+                backoff.spin();
+            }
+        }
+    }
+
+    /// Tries to lock for an upgradable read without waiting.
+    ///
+    /// Fails if a writer holds the lock or is waiting for it, or if there already is an
+    /// upgradable reader.
+    pub fn try_upgradable_read(&self) -> Option<SpinRwLockUpgradableGuard<'_, T>> {
+        self.state
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |state| {
+                (state & (WRITER | UPGRADABLE | PENDING) == 0).then_some(state | UPGRADABLE)
+            })
+            .ok()
+            .map(|_| self.upgradable_guard())
+    }
+
+    /// Locks for an upgradable read, spinning until it is available.
+    pub fn upgradable_read(&self) -> SpinRwLockUpgradableGuard<'_, T> {
+        let mut backoff = Backoff::new();
+        loop {
+            if let Some(guard) = self.try_upgradable_read() {
+                return guard;
+            }
+            while self.state.load(Ordering::Relaxed) & (WRITER | UPGRADABLE | PENDING) != 0 {
+                backoff.spin();
+            }
+        }
+    }
+
+    /// Tries to lock for writing without waiting.
+    pub fn try_write(&self) -> Option<SpinRwLockWriteGuard<'_, T>> {
+        self.try_acquire_writer(0).then(|| self.write_guard())
+    }
+
+    /// Locks for writing, spinning until all other guards are dropped.
+    pub fn write(&self) -> SpinRwLockWriteGuard<'_, T> {
+        self.acquire_writer(0);
+        self.write_guard()
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// This is safe because the mutable reference guarantees that no other thread accesses it.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.with_mut(|ptr| unsafe { &mut *ptr })
+    }
+
+    /// Consumes the lock, returning the contained value.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: Default> Default for SpinRwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for SpinRwLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Debug> Debug for SpinRwLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("SpinRwLock");
+        match self.try_read() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+/// A guard that releases the shared read access of a [`SpinRwLock`] when dropped.
+pub struct SpinRwLockReadGuard<'a, T> {
+    lock: &'a SpinRwLock<T>,
+    data: ManuallyDrop<ConstPtr<T>>,
+}
+
+unsafe impl<T: Sync> Sync for SpinRwLockReadGuard<'_, T> {}
+
+impl<T> Deref for SpinRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { ConstPtr::deref(&self.data) }
+    }
+}
+
+impl<T> Drop for SpinRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.data) };
+        self.lock.state.fetch_sub(READER, Ordering::Release);
+    }
+}
+
+impl<T: Debug> Debug for SpinRwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// A guard that releases the upgradable read access of a [`SpinRwLock`] when dropped.
+pub struct SpinRwLockUpgradableGuard<'a, T> {
+    lock: &'a SpinRwLock<T>,
+    data: ManuallyDrop<ConstPtr<T>>,
+}
+
+unsafe impl<T: Sync> Sync for SpinRwLockUpgradableGuard<'_, T> {}
+
+impl<'a, T> SpinRwLockUpgradableGuard<'a, T> {
+    /// Ends the access without releasing the lock.
+    fn into_lock(self) -> &'a SpinRwLock<T> {
+        let mut this = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::drop(&mut this.data) };
+        this.lock
+    }
+
+    /// Upgrades to a write guard, spinning until all plain readers are gone.
+    pub fn upgrade(self) -> SpinRwLockWriteGuard<'a, T> {
+        let lock = self.into_lock();
+        lock.acquire_writer(UPGRADABLE);
+        lock.write_guard()
+    }
+
+    /// Tries to upgrade to a write guard without waiting.
+    pub fn try_upgrade(self) -> Result<SpinRwLockWriteGuard<'a, T>, Self> {
+        if self.lock.try_acquire_writer(UPGRADABLE) {
+            Ok(self.into_lock().write_guard())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Downgrades to a plain read guard, allowing another upgradable reader in.
+    pub fn downgrade(self) -> SpinRwLockReadGuard<'a, T> {
+        let lock = self.into_lock();
+        lock.state.fetch_add(READER, Ordering::Acquire);
+        lock.state.fetch_and(!UPGRADABLE, Ordering::Release);
+        lock.read_guard()
+    }
+}
+
+impl<T> Deref for SpinRwLockUpgradableGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { ConstPtr::deref(&self.data) }
+    }
+}
+
+impl<T> Drop for SpinRwLockUpgradableGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.data) };
+        self.lock.state.fetch_and(!UPGRADABLE, Ordering::Release);
+    }
+}
+
+impl<T: Debug> Debug for SpinRwLockUpgradableGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// A guard that releases the exclusive write access of a [`SpinRwLock`] when dropped.
+pub struct SpinRwLockWriteGuard<'a, T> {
+    lock: &'a SpinRwLock<T>,
+    data: ManuallyDrop<MutPtr<T>>,
+}
+
+unsafe impl<T: Sync> Sync for SpinRwLockWriteGuard<'_, T> {}
+
+impl<'a, T> SpinRwLockWriteGuard<'a, T> {
+    /// Ends the access without releasing the lock.
+    fn into_lock(self) -> &'a SpinRwLock<T> {
+        let mut this = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::drop(&mut this.data) };
+        this.lock
+    }
+
+    /// Downgrades to a read guard, letting other readers in.
+    pub fn downgrade(self) -> SpinRwLockReadGuard<'a, T> {
+        let lock = self.into_lock();
+        lock.state.fetch_add(READER, Ordering::Acquire);
+        lock.state.fetch_and(!WRITER, Ordering::Release);
+        lock.read_guard()
+    }
+
+    /// Downgrades to an upgradable read guard, letting other readers in.
+    pub fn downgrade_to_upgradable(self) -> SpinRwLockUpgradableGuard<'a, T> {
+        let lock = self.into_lock();
+        // The writer bit is set and the upgradable bit is not, so this swaps them.
+        lock.state.fetch_xor(WRITER | UPGRADABLE, Ordering::Release);
+        lock.upgradable_guard()
+    }
+}
+
+impl<T> Deref for SpinRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { MutPtr::deref(&self.data) }
+    }
+}
+
+impl<T> DerefMut for SpinRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { MutPtr::deref(&self.data) }
+    }
+}
+
+impl<T> Drop for SpinRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.data) };
+        self.lock.state.fetch_and(!WRITER, Ordering::Release);
+    }
+}
+
+impl<T: Debug> Debug for SpinRwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+    #[cfg(feature = "loom")]
+    use loom::thread;
+
+    #[test]
+    fn test_spin_mutex_sync() {
+        try_init_model(|| {
+            let mutex = SpinMutex::new(1);
+
+            let mut guard = mutex.lock();
+            assert!(mutex.is_locked());
+            assert!(mutex.try_lock().is_none());
+            *guard += 1;
+            drop(guard);
+
+            assert!(!mutex.is_locked());
+            assert_eq!(*mutex.lock_with_backoff(), 2);
+            assert_eq!(mutex.into_inner(), 2);
+        });
+    }
+
+    #[test]
+    fn test_spin_rw_lock_sync() {
+        try_init_model(|| {
+            let lock = SpinRwLock::new(1);
+
+            let read = lock.read();
+            let upgradable = lock.upgradable_read();
+            assert!(lock.try_upgradable_read().is_none());
+            assert!(lock.try_write().is_none());
+
+            // A plain reader is still in, so the upgrade has to wait.
+            let upgradable = upgradable.try_upgrade().unwrap_err();
+            drop(read);
+            let mut write = upgradable.try_upgrade().unwrap();
+            assert!(lock.try_read().is_none());
+            *write += 1;
+
+            let read = write.downgrade();
+            assert_eq!(*read, 2);
+            assert!(lock.try_read().is_some());
+            drop(read);
+
+            let write = lock.write();
+            let upgradable = write.downgrade_to_upgradable();
+            assert!(lock.try_read().is_some());
+            let read = upgradable.downgrade();
+            assert!(lock.try_upgradable_read().is_some());
+            drop(read);
+
+            assert_eq!(lock.into_inner(), 2);
+        });
+    }
+
+    #[test]
+    fn test_spin_rw_lock_writer_preference() {
+        try_init_model(|| {
+            let lock = SpinRwLock::new(0);
+
+            let read = lock.read();
+            // Simulate a writer that started waiting for the reader.
+            lock.state.fetch_or(PENDING, Ordering::Relaxed);
+            assert!(lock.try_read().is_none());
+            assert!(lock.try_upgradable_read().is_none());
+            drop(read);
+
+            assert!(lock.try_write().is_some());
+            assert!(lock.try_read().is_some());
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_spin_mutex_loom_exclusion() {
+        try_init_model(|| {
+            let mutex: Arc<SpinMutex<u32>> = Arc::new(SpinMutex::new(0));
+
+            let threads: [_; 2] = core::array::from_fn(|i| thread::spawn({
+                let mutex = mutex.clone();
+                move || {
+                    // The cell reports a data race if two guards ever overlap.
+                    let mut guard = if i == 0 { mutex.lock() } else { mutex.lock_with_backoff() };
+                    *guard += 1;
+                }
+            }));
+
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(*mutex.lock(), 2);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_spin_rw_lock_loom_read_write() {
+        try_init_model(|| {
+            let lock: Arc<SpinRwLock<(u32, u32)>> = Arc::new(SpinRwLock::new((0, 0)));
+
+            let writer = thread::spawn({
+                let lock = lock.clone();
+                move || {
+                    let mut guard = lock.write();
+                    guard.0 += 1;
+                    guard.1 += 1;
+                }
+            });
+
+            let reader = thread::spawn({
+                let lock = lock.clone();
+                move || {
+                    let guard = lock.read();
+                    assert_eq!(guard.0, guard.1);
+                }
+            });
+
+            writer.join().unwrap();
+            reader.join().unwrap();
+            assert_eq!(*lock.read(), (1, 1));
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom")]
+    fn test_spin_rw_lock_loom_upgrade() {
+        try_init_model(|| {
+            let lock: Arc<SpinRwLock<u32>> = Arc::new(SpinRwLock::new(0));
+
+            let upgrader = thread::spawn({
+                let lock = lock.clone();
+                move || {
+                    let guard = lock.upgradable_read();
+                    let seen = *guard;
+                    let mut guard = guard.upgrade();
+                    // No writer can get in between the read and the upgrade.
+                    assert_eq!(*guard, seen);
+                    *guard += 1;
+                }
+            });
+
+            *lock.write() += 1;
+
+            upgrader.join().unwrap();
+            assert_eq!(*lock.read(), 2);
+        });
+    }
+}
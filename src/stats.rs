@@ -0,0 +1,396 @@
+//! Contention statistics of atomics (`stats` crate feature).
+//!
+//! With the `stats` feature, every [`Atomic`] counts its operations by kind, its failed
+//! compare-exchanges and the retries of its `fetch_update`s, returned by [`Atomic::stats`].
+//! Atomics named with [`Atomic::set_stats_name`] are also listed by [`registry`].
+//!
+//! So that the counters do not become contended themselves, each thread batches its counts, and
+//! adds them to the shared counters every [`BATCH`] operations on an atomic, when it exits, or
+//! when [`flush`] is called. [`Atomic::stats`] and [`registry`] flush the current thread first,
+//! but miss the counts still batched by the other running threads.
+//!
+//! # Examples
+//! ```
+//! use atomiq::prelude::*;
+//! use atomiq::stats;
+//! # use atomiq::try_init_model;
+//!
+//! # try_init_model(|| {
+//! let counter: Atomic<u32> = Atomic::from(0);
+//! counter.set_stats_name("counter");
+//!
+//! counter.fetch_add(1, Ordering::Relaxed);
+//! let _ = counter.compare_exchange(0, 2, Ordering::AcqRel, Ordering::Acquire);
+//!
+//! let stats = counter.stats();
+//! assert_eq!(stats.arithmetic, 1);
+//! assert_eq!(stats.compare_exchange_failures, 1);
+//! assert!(stats::registry().iter().any(|(name, _)| *name == "counter"));
+//! # });
+//! ```
+
+use core::cell::RefCell;
+use core::fmt::{self, Debug, Display, Formatter};
+use core::marker::PhantomData;
+use core::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::vec::Vec;
+use crate::prelude::*;
+
+/// How many operations on an atomic a thread batches before adding them to its counters.
+pub const BATCH: u64 = 64;
+
+/// How many atomics a thread batches operations on at once.
+const SLOTS: usize = 16;
+
+/// The operation counts of an atomic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Loads.
+    pub loads: u64,
+    /// Stores.
+    pub stores: u64,
+    /// Swaps.
+    pub swaps: u64,
+    /// Strong and weak compare-exchanges, including failed ones.
+    pub compare_exchanges: u64,
+    /// Failed compare-exchanges.
+    pub compare_exchange_failures: u64,
+    /// `fetch_update`s, including failed ones.
+    pub fetch_updates: u64,
+    /// Times a `fetch_update` called its function again, after the value changed under it.
+    pub fetch_update_retries: u64,
+    /// `fetch_and`, `fetch_nand`, `fetch_or` and `fetch_xor` operations.
+    pub bitwise: u64,
+    /// `fetch_add`, `fetch_sub`, `fetch_min` and `fetch_max` operations.
+    pub arithmetic: u64,
+}
+
+impl Stats {
+    /// Returns the number of operations of all kinds.
+    pub fn operations(&self) -> u64 {
+        self.loads
+            + self.stores
+            + self.swaps
+            + self.compare_exchanges
+            + self.fetch_updates
+            + self.bitwise
+            + self.arithmetic
+    }
+
+    /// Returns the number of compare-exchange failures and `fetch_update` retries, which both
+    /// mean that another thread changed the value first.
+    pub fn contended(&self) -> u64 {
+        self.compare_exchange_failures + self.fetch_update_retries
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} operations ({} loads, {} stores, {} swaps, {} compare-exchanges, {} fetch_updates, {} bitwise, {} arithmetic), \
+             {} compare-exchange failures, {} fetch_update retries",
+            self.operations(),
+            self.loads,
+            self.stores,
+            self.swaps,
+            self.compare_exchanges,
+            self.fetch_updates,
+            self.bitwise,
+            self.arithmetic,
+            self.compare_exchange_failures,
+            self.fetch_update_retries,
+        )
+    }
+}
+
+/// The kind of a counted operation.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Load,
+    Store,
+    Swap,
+    CompareExchange,
+    CompareExchangeFailure,
+    FetchUpdate,
+    FetchUpdateRetry,
+    Bitwise,
+    Arithmetic,
+}
+
+const KINDS: usize = Kind::Arithmetic as usize + 1;
+
+/// The shared operation counters of an atomic.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct Counters {
+    counts: [AtomicU64; KINDS],
+    name: OnceLock<&'static str>,
+}
+
+impl Counters {
+    fn add(&self, counts: &[u64; KINDS]) {
+        for (counter, count) in self.counts.iter().zip(counts) {
+            if *count > 0 {
+                counter.fetch_add(*count, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        let count = |kind: Kind| self.counts[kind as usize].load(Ordering::Relaxed);
+        Stats {
+            loads: count(Kind::Load),
+            stores: count(Kind::Store),
+            swaps: count(Kind::Swap),
+            compare_exchanges: count(Kind::CompareExchange),
+            compare_exchange_failures: count(Kind::CompareExchangeFailure),
+            fetch_updates: count(Kind::FetchUpdate),
+            fetch_update_retries: count(Kind::FetchUpdateRetry),
+            bitwise: count(Kind::Bitwise),
+            arithmetic: count(Kind::Arithmetic),
+        }
+    }
+}
+
+/// The counts batched by a thread for an atomic.
+struct Slot {
+    counters: Arc<Counters>,
+    counts: [u64; KINDS],
+    operations: u64,
+}
+
+impl Slot {
+    fn flush(&mut self) {
+        self.counters.add(&self.counts);
+        self.counts = [0; KINDS];
+        self.operations = 0;
+    }
+}
+
+/// The counts batched by a thread, in a direct-mapped cache of atomics.
+#[derive(Default)]
+struct Batch {
+    slots: [Option<Slot>; SLOTS],
+}
+
+impl Batch {
+    fn count(&mut self, counters: &Arc<Counters>, kind: Kind, count: u64) {
+        let index = (Arc::as_ptr(counters) as usize / size_of::<Counters>()) % SLOTS;
+        let slot = match &mut self.slots[index] {
+            Some(slot) if Arc::ptr_eq(&slot.counters, counters) => slot,
+            entry => {
+                if let Some(mut evicted) = entry.take() {
+                    evicted.flush();
+                }
+                entry.insert(Slot { counters: counters.clone(), counts: [0; KINDS], operations: 0 })
+            }
+        };
+
+        slot.counts[kind as usize] += count;
+        slot.operations += 1;
+        if slot.operations >= BATCH {
+            slot.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        for slot in self.slots.iter_mut().flatten() {
+            slot.flush();
+        }
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+std::thread_local! {
+    static THREAD_BATCH: RefCell<Batch> = RefCell::new(Batch::default());
+}
+
+/// Adds the counts batched by the current thread to the counters of their atomics.
+pub fn flush() {
+    let _ = THREAD_BATCH.try_with(|batch| batch.borrow_mut().flush());
+}
+
+static REGISTRY: Mutex<Vec<(&'static str, Weak<Counters>)>> = Mutex::new(Vec::new());
+
+/// Returns the names and counts of the atomics named with [`Atomic::set_stats_name`], in naming
+/// order, after flushing the current thread.
+///
+/// Dropped atomics are listed until no thread batches counts for them anymore.
+pub fn registry() -> Vec<(&'static str, Stats)> {
+    flush();
+    let mut registry = REGISTRY.lock().unwrap_or_else(|error| error.into_inner());
+    registry.retain(|(_, counters)| counters.strong_count() > 0);
+    registry
+        .iter()
+        .filter_map(|(name, counters)| Some((*name, counters.upgrade()?.stats())))
+        .collect()
+}
+
+/// A provider counting the operations of the provider it wraps.
+#[doc(hidden)]
+pub struct Counted<A, P> {
+    inner: P,
+    counters: Arc<Counters>,
+    atom: PhantomData<fn() -> A>,
+}
+
+impl<A, P> Counted<A, P> {
+    fn new(inner: P) -> Self {
+        Self { inner, counters: Arc::default(), atom: PhantomData }
+    }
+
+    pub(crate) fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub(crate) fn counters(&self) -> &Arc<Counters> {
+        &self.counters
+    }
+
+    pub(crate) fn count(&self, kind: Kind, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let batched = THREAD_BATCH.try_with(|batch| batch.borrow_mut().count(&self.counters, kind, count));
+        if batched.is_err() {
+            // The thread is exiting, so count directly.
+            self.counters.counts[kind as usize].fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<A, P: Debug> Debug for Counted<A, P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<A, P: Default> Default for Counted<A, P> {
+    fn default() -> Self {
+        Self::new(P::default())
+    }
+}
+
+impl<A, P: From<A>> From<A> for Counted<A, P> {
+    fn from(value: A) -> Self {
+        Self::new(P::from(value))
+    }
+}
+
+impl<T: Atomizable> Atomic<T> {
+    /// Returns the operation counts of the atomic, after flushing the current thread.
+    ///
+    /// Atoms implemented outside of this crate are not counted, and always return zero counts.
+    ///
+    /// See the [`stats`](crate::stats) module for more information.
+    pub fn stats(&self) -> Stats {
+        flush();
+        T::Atom::stats_counters(&self.0).map_or_else(Stats::default, |counters| counters.stats())
+    }
+
+    /// Names the atomic, listing it in the [`registry`].
+    ///
+    /// Only the first name given to an atomic is kept, and atoms implemented outside of this
+    /// crate are never listed.
+    pub fn set_stats_name(&self, name: &'static str) {
+        let Some(counters) = T::Atom::stats_counters(&self.0) else {
+            return;
+        };
+        if counters.name.set(name).is_ok() {
+            REGISTRY
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .push((name, Arc::downgrade(counters)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+
+    #[test]
+    fn test_stats_counts() {
+        try_init_model(|| {
+            let value: Atomic<u32> = Atomic::from(0);
+            value.store(1, Ordering::Release);
+            value.load(Ordering::Acquire);
+            value.fetch_or(2, Ordering::AcqRel);
+            value.fetch_add(1, Ordering::AcqRel);
+            let _ = value.compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire);
+            let _ = value.compare_exchange(4, 5, Ordering::AcqRel, Ordering::Acquire);
+
+            let mut first = true;
+            let _ = value.fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                if core::mem::take(&mut first) {
+                    // Change the value under the update, so that it is retried.
+                    value.store(current + 1, Ordering::Release);
+                }
+                Some(current + 1)
+            });
+
+            let stats = value.stats();
+            assert_eq!(stats.loads, 1);
+            assert_eq!(stats.stores, 2);
+            assert_eq!(stats.bitwise, 1);
+            assert_eq!(stats.arithmetic, 1);
+            assert_eq!(stats.compare_exchanges, 2);
+            assert_eq!(stats.compare_exchange_failures, 1);
+            assert_eq!(stats.fetch_updates, 1);
+            // `shuttle` runs `fetch_update` as one step, calling the function only once.
+            if cfg!(not(all(feature = "shuttle", not(feature = "loom")))) {
+                assert!(stats.fetch_update_retries >= 1);
+            }
+            assert_eq!(stats.operations(), 8);
+        });
+    }
+
+    /// An atom implemented like outside of the crate, without counters.
+    #[derive(Clone, Copy, Debug)]
+    struct External;
+
+    #[derive(Debug, Default)]
+    struct ExternalProvider;
+
+    impl From<External> for ExternalProvider {
+        fn from(_: External) -> Self {
+            Self
+        }
+    }
+
+    impl Atom for External {
+        type Provider = ExternalProvider;
+
+        fn load(_: &Self::Provider, _: Ordering) -> Self { unimplemented!() }
+        fn store(_: &Self::Provider, _: Self, _: Ordering) { unimplemented!() }
+        fn swap(_: &Self::Provider, _: Self, _: Ordering) -> Self { unimplemented!() }
+        fn compare_exchange(_: &Self::Provider, _: Self, _: Self, _: Ordering, _: Ordering) -> Result<Self, Self> { unimplemented!() }
+        fn compare_exchange_weak(_: &Self::Provider, _: Self, _: Self, _: Ordering, _: Ordering) -> Result<Self, Self> { unimplemented!() }
+        fn fetch_update<F>(_: &Self::Provider, _: Ordering, _: Ordering, _: F) -> Result<Self, Self>
+        where
+            F: FnMut(Self) -> Option<Self>,
+        {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_stats_external_atom() {
+        let value: Atomic<External> = Atomic::default();
+        value.set_stats_name("test_stats_external_atom");
+
+        assert_eq!(value.stats(), Stats::default());
+        assert!(registry().iter().all(|(name, _)| *name != "test_stats_external_atom"));
+    }
+}
@@ -0,0 +1,106 @@
+//! Validation of the orderings passed to the crate primitives (`strict` crate feature).
+//!
+//! Without the feature, the checks do nothing.
+
+use crate::prelude::*;
+
+/// Returns how strongly an ordering orders the loads of an operation.
+fn load_strength(ordering: Ordering) -> u8 {
+    match ordering {
+        Ordering::Relaxed | Ordering::Release => 0,
+        Ordering::Acquire | Ordering::AcqRel => 1,
+        _ => 2,
+    }
+}
+
+/// Panics if the failure ordering of an exchange is stronger than its success ordering.
+///
+/// A successful exchange also loads the value, so it should not order its load more weakly than
+/// a failed one.
+#[inline]
+#[track_caller]
+pub(crate) fn check_exchange(api: &str, success: Ordering, failure: Ordering) {
+    #[cfg(feature = "strict")]
+    if load_strength(failure) > load_strength(success) {
+        let suggestion = match (failure, success) {
+            (Ordering::SeqCst, _) => Ordering::SeqCst,
+            (_, Ordering::Release) => Ordering::AcqRel,
+            _ => Ordering::Acquire,
+        };
+        panic!(
+            "`{}` called with the failure ordering `{:?}`, stronger than the success ordering `{:?}`; \
+             use `{:?}` for success or a weaker ordering for failure",
+            api, failure, success, suggestion,
+        );
+    }
+}
+
+/// Panics if a store publishing the value of an [`AtomicOption`](crate::option::AtomicOption)
+/// is `Relaxed`, which may set the `is_some` flag before the value is visible.
+#[inline]
+#[track_caller]
+pub(crate) fn check_publish(api: &str, ordering: Ordering) {
+    #[cfg(feature = "strict")]
+    if ordering == Ordering::Relaxed {
+        panic!(
+            "`{}` called with `Relaxed`, which may publish the `is_some` flag before the value; \
+             use `Release` or stronger",
+            api,
+        );
+    }
+}
+
+/// Panics if a load reading the value of an [`AtomicOption`](crate::option::AtomicOption) is
+/// `Relaxed`, which may read the value before it is visible.
+#[inline]
+#[track_caller]
+pub(crate) fn check_consume(api: &str, ordering: Ordering) {
+    #[cfg(feature = "strict")]
+    if ordering == Ordering::Relaxed {
+        panic!(
+            "`{}` called with `Relaxed`, which may read the value before the `is_some` flag \
+             published it; use `Acquire` or stronger",
+            api,
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "strict")]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::option::AtomicOption;
+    use crate::try_init_model;
+
+    #[test]
+    #[should_panic(expected = "`AtomicOption::store` called with `Relaxed`")]
+    fn test_strict_option_store_relaxed() {
+        try_init_model(|| {
+            let option: AtomicOption<u32> = AtomicOption::none();
+            option.store_some(1, Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "failure ordering `SeqCst`, stronger than the success ordering `Acquire`")]
+    fn test_strict_exchange_failure_stronger() {
+        try_init_model(|| {
+            let value: Atomic<u32> = Atomic::from(0);
+            let _ = value.compare_exchange(0, 1, Ordering::Acquire, Ordering::SeqCst);
+        });
+    }
+
+    #[test]
+    fn test_strict_valid_orderings() {
+        try_init_model(|| {
+            let option: AtomicOption<u32> = AtomicOption::none();
+            option.store_some(1, Ordering::Release);
+            assert_eq!(option.load(Ordering::Acquire), Some(1));
+
+            let value: Atomic<u32> = Atomic::from(0);
+            assert_eq!(value.compare_exchange(0, 1, Ordering::Release, Ordering::Relaxed), Ok(0));
+            assert_eq!(value.fetch_update(Ordering::AcqRel, Ordering::Acquire, |value| Some(value + 1)), Ok(1));
+        });
+    }
+}
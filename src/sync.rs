@@ -0,0 +1,152 @@
+//! Threads and synchronization primitives of the current backend (`std` crate feature).
+//!
+//! Re-exports the `loom` implementation of each item when the `loom` feature is enabled, the
+//! `shuttle` one with the `shuttle` feature, and the standard library one otherwise, so that the
+//! same code can be run normally and model-checked without its own `cfg` switches.
+//!
+//! # Examples
+//! ```
+//! use atomiq::prelude::*;
+//! use atomiq::sync::{thread, Mutex};
+//! # use atomiq::try_init_model;
+//!
+//! # try_init_model(|| {
+//! let counter = Arc::new(Mutex::new(0));
+//!
+//! let handle = thread::spawn({
+//!     let counter = counter.clone();
+//!     move || *counter.lock().unwrap() += 1
+//! });
+//!
+//! *counter.lock().unwrap() += 1;
+//! handle.join().unwrap();
+//!
+//! assert_eq!(*counter.lock().unwrap(), 2);
+//! # });
+//! ```
+//!
+//! See [`cell::UnsafeCell`] and [`lazy_static!`] for more information.
+
+use cfg_if::cfg_if;
+
+pub use crate::atom::{fence, Arc};
+
+cfg_if!(
+    if #[cfg(feature = "loom")] {
+        pub use loom::sync::{
+            Barrier, Condvar, LockResult, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+            TryLockResult, WaitTimeoutResult, mpsc,
+        };
+        pub use loom::{lazy_static, thread_local};
+
+        /// Threads of the current backend.
+        pub mod thread {
+            pub use loom::thread::{
+                AccessError, Builder, JoinHandle, LocalKey, Thread, ThreadId, current, panicking, park, spawn,
+                yield_now,
+            };
+        }
+    } else if #[cfg(feature = "shuttle")] {
+        pub use shuttle::sync::{
+            Barrier, Condvar, LockResult, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+            TryLockResult, WaitTimeoutResult, mpsc,
+        };
+        pub use shuttle::{lazy_static, thread_local};
+
+        /// Threads of the current backend.
+        pub mod thread {
+            pub use shuttle::thread::{
+                AccessError, Builder, JoinHandle, LocalKey, Thread, ThreadId, current, panicking, park, spawn,
+                yield_now,
+            };
+        }
+    } else {
+        pub use std::sync::{
+            Barrier, Condvar, LockResult, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+            TryLockResult, WaitTimeoutResult, mpsc,
+        };
+        pub use std::thread_local;
+        pub use crate::__lazy_static as lazy_static;
+
+        /// Threads of the current backend.
+        ///
+        /// With the `race-check` feature, [`spawn`] and [`JoinHandle::join`] order the tracked
+        /// accesses of the threads.
+        pub mod thread {
+            pub use std::thread::{
+                AccessError, Builder, LocalKey, Thread, ThreadId, current, panicking, park, yield_now,
+            };
+            #[cfg(not(feature = "race-check"))]
+            pub use std::thread::{JoinHandle, spawn};
+            #[cfg(feature = "race-check")]
+            pub use crate::race_check::thread::{JoinHandle, spawn};
+        }
+    }
+);
+
+/// Cells of the current backend.
+///
+/// [`TrackedCell`] checks its accesses with the `race-check` feature, and is an [`UnsafeCell`]
+/// otherwise, including under `loom` and `shuttle`.
+pub mod cell {
+    pub use crate::cell::{ConstPtr, MutPtr, UnsafeCell};
+    #[cfg(all(feature = "race-check", not(any(feature = "loom", feature = "shuttle"))))]
+    pub use crate::race_check::TrackedCell;
+    /// An [`UnsafeCell`], checking its accesses with the `race-check` feature.
+    #[cfg(not(all(feature = "race-check", not(any(feature = "loom", feature = "shuttle")))))]
+    pub type TrackedCell<T> = UnsafeCell<T>;
+}
+
+/// Declares lazily initialized statics, with the syntax of `loom::lazy_static!`.
+///
+/// Under `loom`, the statics are initialized again in every execution of the model.
+///
+/// Use it through [`atomiq::sync::lazy_static!`](crate::sync::lazy_static).
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __lazy_static {
+    ($(#[$attr:meta])* $vis:vis static ref $name:ident : $ty:ty = $init:expr; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: ::std::sync::LazyLock<$ty> = ::std::sync::LazyLock::new(|| $init);
+        $crate::__lazy_static!($($rest)*);
+    };
+    () => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::prelude::*;
+    use crate::try_init_model;
+
+    lazy_static! {
+        static ref ANSWER: Atomic<u32> = Atomic::from(42);
+    }
+
+    #[test]
+    fn test_sync_facade() {
+        try_init_model(|| {
+            let pair = Arc::new((Mutex::new(false), Condvar::new()));
+
+            let handle = thread::spawn({
+                let pair = pair.clone();
+                move || {
+                    let (ready, condvar) = &*pair;
+                    *ready.lock().unwrap() = true;
+                    condvar.notify_one();
+                }
+            });
+
+            let (ready, condvar) = &*pair;
+            let mut guard = ready.lock().unwrap();
+            while !*guard {
+                guard = condvar.wait(guard).unwrap();
+            }
+            drop(guard);
+            handle.join().unwrap();
+
+            assert_eq!(ANSWER.fetch_add(1, Ordering::Relaxed), 42);
+        });
+    }
+}
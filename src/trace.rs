@@ -0,0 +1,370 @@
+//! Recording of atomic operations (`trace` crate feature).
+//!
+//! With the `trace` feature, every operation on an [`Atomic`] is recorded as an [`Event`] and sent
+//! to the current [`Sink`]. By default, events are logged at the trace level with the
+//! `atomiq::trace` target. [`RingBuffer`] keeps the latest events in memory instead, so that the
+//! history can be printed when an assertion fails.
+//!
+//! Events show the address of the atomic and its values as atoms, unless the atomic is named with
+//! [`Atomic::set_trace_name`], which shows the name and the unpacked values instead.
+//!
+//! # Examples
+//! ```
+//! use atomiq::prelude::*;
+//! use atomiq::trace::{self, RingBuffer};
+//! # use atomiq::try_init_model;
+//!
+//! static EVENTS: RingBuffer = RingBuffer::new(64);
+//!
+//! # try_init_model(|| {
+//! trace::set_sink(&EVENTS);
+//!
+//! let counter: Atomic<u32> = Atomic::from(0);
+//! counter.set_trace_name("counter");
+//! counter.fetch_add(1, Ordering::Relaxed);
+//!
+//! assert_eq!(counter.load(Ordering::Relaxed), 1, "history:\n{}", EVENTS);
+//! # trace::reset_sink();
+//! # });
+//! ```
+
+use core::fmt::{self, Debug, Display, Formatter};
+use std::collections::VecDeque;
+use std::format;
+use std::string::String;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::vec::Vec;
+use crate::prelude::*;
+use crate::sync::thread::{self, ThreadId};
+
+/// The kind of an atomic operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum Operation {
+    Load,
+    Store,
+    Swap,
+    CompareExchange,
+    CompareExchangeWeak,
+    FetchUpdate,
+    FetchAnd,
+    FetchNand,
+    FetchOr,
+    FetchXor,
+    FetchAdd,
+    FetchSub,
+    FetchMin,
+    FetchMax,
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::Load => "load",
+            Operation::Store => "store",
+            Operation::Swap => "swap",
+            Operation::CompareExchange => "compare_exchange",
+            Operation::CompareExchangeWeak => "compare_exchange_weak",
+            Operation::FetchUpdate => "fetch_update",
+            Operation::FetchAnd => "fetch_and",
+            Operation::FetchNand => "fetch_nand",
+            Operation::FetchOr => "fetch_or",
+            Operation::FetchXor => "fetch_xor",
+            Operation::FetchAdd => "fetch_add",
+            Operation::FetchSub => "fetch_sub",
+            Operation::FetchMin => "fetch_min",
+            Operation::FetchMax => "fetch_max",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A recorded atomic operation.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Event {
+    /// The name given with [`Atomic::set_trace_name`], if any.
+    pub name: Option<&'static str>,
+    /// The address of the atomic.
+    pub address: usize,
+    /// The kind of the operation.
+    pub operation: Operation,
+    /// The ordering the operation was performed with, which is the failure ordering of failed
+    /// exchanges and updates.
+    pub ordering: Ordering,
+    /// The value before the operation, if it was read.
+    pub old: Option<String>,
+    /// The value after the operation, if it was written.
+    pub new: Option<String>,
+    /// The thread performing the operation.
+    pub thread: ThreadId,
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ", self.thread)?;
+        match self.name {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "{:#x}", self.address)?,
+        }
+        write!(f, " {}({:?})", self.operation, self.ordering)?;
+        match (&self.old, &self.new) {
+            (None, None) => Ok(()),
+            (old, new) => write!(f, ": {} -> {}", old.as_deref().unwrap_or("_"), new.as_deref().unwrap_or("_")),
+        }
+    }
+}
+
+/// A destination for recorded events.
+pub trait Sink: Sync {
+    /// Returns whether events should be recorded, to skip formatting them otherwise.
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// Records an event.
+    fn record(&self, event: &Event);
+}
+
+/// Logs events at the trace level with the `atomiq::trace` target. (default)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogSink;
+
+impl Sink for LogSink {
+    fn enabled(&self) -> bool {
+        log::log_enabled!(target: "atomiq::trace", log::Level::Trace)
+    }
+
+    fn record(&self, event: &Event) {
+        log::trace!(target: "atomiq::trace", "{}", event);
+    }
+}
+
+/// Keeps the latest events in memory.
+///
+/// Displaying the buffer prints its events, one per line.
+#[derive(Debug)]
+pub struct RingBuffer {
+    capacity: usize,
+    events: Mutex<VecDeque<Event>>,
+}
+
+impl RingBuffer {
+    /// Creates a buffer keeping at most `capacity` events.
+    pub const fn new(capacity: usize) -> Self {
+        Self { capacity, events: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Returns the recorded events, oldest first.
+    pub fn events(&self) -> Vec<Event> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Removes all recorded events.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Event>> {
+        // Events are only pushed and popped, so a panic cannot leave the buffer inconsistent.
+        self.events.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl Sink for RingBuffer {
+    fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    fn record(&self, event: &Event) {
+        let mut events = self.lock();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event.clone());
+    }
+}
+
+impl Display for RingBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for event in self.lock().iter() {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+static SINK: RwLock<&'static dyn Sink> = RwLock::new(&LogSink);
+
+/// Sends the events of all threads to `sink`.
+pub fn set_sink(sink: &'static dyn Sink) {
+    *SINK.write().unwrap_or_else(|error| error.into_inner()) = sink;
+}
+
+/// Sends the events back to the default [`LogSink`].
+pub fn reset_sink() {
+    set_sink(&LogSink);
+}
+
+fn sink() -> &'static dyn Sink {
+    *SINK.read().unwrap_or_else(|error| error.into_inner())
+}
+
+/// The name of a traced provider and the formatting of its unpacked values.
+struct Name<A> {
+    name: &'static str,
+    format: fn(A) -> String,
+}
+
+/// A provider recording the operations of the provider it wraps.
+#[doc(hidden)]
+pub struct Traced<A, P> {
+    inner: P,
+    name: OnceLock<Name<A>>,
+}
+
+impl<A: Atom, P> Traced<A, P> {
+    pub(crate) fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub(crate) fn set_name(&self, name: &'static str, format: fn(A) -> String) {
+        let _ = self.name.set(Name { name, format });
+    }
+
+    pub(crate) fn record(&self, operation: Operation, ordering: Ordering, old: Option<A>, new: Option<A>) {
+        let sink = sink();
+        if !sink.enabled() {
+            return;
+        }
+
+        let name = self.name.get();
+        let show = |value: A| match name {
+            Some(name) => (name.format)(value),
+            None => format!("{:?}", value),
+        };
+        sink.record(&Event {
+            name: name.map(|name| name.name),
+            address: &self.inner as *const P as usize,
+            operation,
+            ordering,
+            old: old.map(show),
+            new: new.map(show),
+            thread: thread::current().id(),
+        });
+    }
+
+    pub(crate) fn record_exchange(
+        &self,
+        operation: Operation,
+        success: Ordering,
+        failure: Ordering,
+        result: Result<A, A>,
+        new: Option<A>,
+    ) {
+        match result {
+            Ok(old) => self.record(operation, success, Some(old), new),
+            Err(old) => self.record(operation, failure, Some(old), None),
+        }
+    }
+}
+
+impl<A, P: Debug> Debug for Traced<A, P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<A, P: Default> Default for Traced<A, P> {
+    fn default() -> Self {
+        Self { inner: P::default(), name: OnceLock::new() }
+    }
+}
+
+impl<A, P: From<A>> From<A> for Traced<A, P> {
+    fn from(value: A) -> Self {
+        Self { inner: P::from(value), name: OnceLock::new() }
+    }
+}
+
+impl<T: Atomizable + Debug> Atomic<T> {
+    /// Names the atomic in trace events, which then also show its values unpacked.
+    ///
+    /// Only the first name given to an atomic is kept.
+    pub fn set_trace_name(&self, name: &'static str) {
+        T::Atom::set_trace_name(&self.0, name, |atom| format!("{:?}", T::unpack(atom)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use crate::try_init_model;
+
+    static EVENTS: RingBuffer = RingBuffer::new(1024);
+
+    #[test]
+    fn test_trace_ring_buffer() {
+        try_init_model(|| {
+            set_sink(&EVENTS);
+
+            let value: Atomic<i32> = Atomic::from(1);
+            value.set_trace_name("test_trace_ring_buffer");
+            value.fetch_add(2, Ordering::AcqRel);
+            let _ = value.compare_exchange(0, 5, Ordering::AcqRel, Ordering::Acquire);
+            value.store(-1, Ordering::Release);
+
+            reset_sink();
+
+            let events: Vec<_> = EVENTS
+                .events()
+                .into_iter()
+                .filter(|event| event.name == Some("test_trace_ring_buffer"))
+                .map(|event| format!("{} {:?} {:?} {:?}", event.operation, event.ordering, event.old, event.new))
+                .collect();
+            assert_eq!(events, [
+                r#"fetch_add AcqRel Some("1") Some("3")"#,
+                r#"compare_exchange Acquire Some("3") None"#,
+                r#"store Release None Some("-1")"#,
+            ]);
+            EVENTS.clear();
+        });
+    }
+
+    /// An atom implemented like outside of the crate, without recording.
+    #[derive(Clone, Copy, Debug)]
+    struct External;
+
+    #[derive(Debug, Default)]
+    struct ExternalProvider;
+
+    impl From<External> for ExternalProvider {
+        fn from(_: External) -> Self {
+            Self
+        }
+    }
+
+    impl Atom for External {
+        type Provider = ExternalProvider;
+
+        fn load(_: &Self::Provider, _: Ordering) -> Self { unimplemented!() }
+        fn store(_: &Self::Provider, _: Self, _: Ordering) { unimplemented!() }
+        fn swap(_: &Self::Provider, _: Self, _: Ordering) -> Self { unimplemented!() }
+        fn compare_exchange(_: &Self::Provider, _: Self, _: Self, _: Ordering, _: Ordering) -> Result<Self, Self> { unimplemented!() }
+        fn compare_exchange_weak(_: &Self::Provider, _: Self, _: Self, _: Ordering, _: Ordering) -> Result<Self, Self> { unimplemented!() }
+        fn fetch_update<F>(_: &Self::Provider, _: Ordering, _: Ordering, _: F) -> Result<Self, Self>
+        where
+            F: FnMut(Self) -> Option<Self>,
+        {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_trace_external_atom() {
+        let value: Atomic<External> = Atomic::default();
+        // Atoms implemented outside of the crate are not recorded, so naming them does nothing.
+        value.set_trace_name("test_trace_external_atom");
+    }
+}
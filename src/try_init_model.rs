@@ -1,13 +1,19 @@
 use cfg_if::cfg_if;
+use core::time::Duration;
+
+/// How many schedules `shuttle` explores, unless set with [`ModelBuilder::iterations`].
+#[cfg(all(feature = "shuttle", not(feature = "loom")))]
+const SHUTTLE_ITERATIONS: usize = 100;
 
 /// Initializes the model for testing.
-/// 
-/// If there is no model to initialize, this function does nothing.
-/// 
+///
+/// With `shuttle`, `f` is run under 100 random schedules. If there is no model to initialize,
+/// this function does nothing.
+///
 /// # Example
 /// ```
 /// use atomiq::try_init_model;
-/// 
+///
 /// try_init_model(|| {
 ///    // Perform atomic operations here.
 /// });
@@ -19,6 +25,426 @@ where
 {
     #[cfg(feature = "loom")]
     loom::model(f);
-    #[cfg(not(feature = "loom"))]
+    #[cfg(all(feature = "shuttle", not(feature = "loom")))]
+    shuttle::check_random(f, SHUTTLE_ITERATIONS);
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
     f();
-}
\ No newline at end of file
+}
+
+/// The scheduler choosing the thread interleavings explored with `shuttle`.
+///
+/// # Example
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use atomiq::prelude::*;
+/// use atomiq::sync::thread;
+/// use atomiq::{try_init_model_with, ModelBuilder, Scheduler};
+///
+/// let builder = ModelBuilder::new()
+///     .scheduler(Scheduler::Pct { depth: 2 })
+///     .iterations(50);
+///
+/// try_init_model_with(builder, || {
+///     let counter: Arc<Atomic<u32>> = Arc::new(Atomic::from(0));
+///     let other = thread::spawn({
+///         let counter = counter.clone();
+///         move || counter.fetch_add(1, Ordering::Relaxed)
+///     });
+///     counter.fetch_add(1, Ordering::Relaxed);
+///     other.join().unwrap();
+///     assert_eq!(counter.load(Ordering::Relaxed), 2);
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Scheduler {
+    /// Picks a random runnable thread at every step. (default)
+    #[default]
+    Random,
+    /// Probabilistic concurrency testing, which finds every bug needing at most `depth` ordering
+    /// constraints between threads with a guaranteed probability in each iteration.
+    Pct {
+        /// The number of ordering constraints, usually 2 or 3.
+        depth: usize,
+    },
+}
+
+/// The level at which [`ModelBuilder`] logs the iteration numbers.
+///
+/// Messages are logged through the `log` crate with the `log` crate feature, and dropped
+/// otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    /// The `error` level.
+    Error,
+    /// The `warn` level.
+    Warn,
+    /// The `info` level.
+    Info,
+    /// The `debug` level.
+    Debug,
+    /// The `trace` level.
+    Trace,
+}
+
+#[cfg(feature = "log")]
+impl From<LogLevel> for log::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => log::Level::Error,
+            LogLevel::Warn => log::Level::Warn,
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Debug => log::Level::Debug,
+            LogLevel::Trace => log::Level::Trace,
+        }
+    }
+}
+
+/// Configuration of the model runner, independent of the backend.
+///
+/// Settings that do not apply to the current backend are ignored, and settings that are not set
+/// keep the backend's defaults. With `loom`, these may be set by `LOOM_*` environment variables.
+/// When both `loom` and `shuttle` are enabled, `loom` is used.
+///
+/// # Example
+/// ```
+/// use atomiq::{try_init_model_with, ModelBuilder};
+///
+/// let builder = ModelBuilder::new()
+///     .preemption_bound(2)
+///     .max_threads(3)
+///     .iterations(10);
+///
+/// try_init_model_with(builder, || {
+///    // Perform atomic operations here.
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct ModelBuilder {
+    name: Option<&'static str>,
+    preemption_bound: Option<usize>,
+    max_threads: Option<usize>,
+    max_branches: Option<usize>,
+    max_duration: Option<Duration>,
+    #[cfg(feature = "loom-checkpoint")]
+    checkpoint_file: Option<&'static str>,
+    #[cfg(feature = "loom-checkpoint")]
+    checkpoint_interval: Option<usize>,
+    log_level: Option<LogLevel>,
+    scheduler: Scheduler,
+    iterations: Option<usize>,
+}
+
+impl Default for ModelBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModelBuilder {
+    /// Creates a builder with the backend's defaults, running once without a model and exploring
+    /// 100 random schedules with `shuttle`.
+    pub const fn new() -> Self {
+        Self {
+            name: None,
+            preemption_bound: None,
+            max_threads: None,
+            max_branches: None,
+            max_duration: None,
+            #[cfg(feature = "loom-checkpoint")]
+            checkpoint_file: None,
+            #[cfg(feature = "loom-checkpoint")]
+            checkpoint_interval: None,
+            log_level: None,
+            scheduler: Scheduler::Random,
+            iterations: None,
+        }
+    }
+
+    /// Sets the name of the model, shown in the logs.
+    pub const fn name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the maximum number of thread preemptions to explore. (`loom` only)
+    pub const fn preemption_bound(mut self, bound: usize) -> Self {
+        self.preemption_bound = Some(bound);
+        self
+    }
+
+    /// Sets the maximum number of threads in the model. (`loom` only)
+    pub const fn max_threads(mut self, threads: usize) -> Self {
+        self.max_threads = Some(threads);
+        self
+    }
+
+    /// Sets the maximum number of branches in one execution. (`loom`, or the maximum number of
+    /// steps with `shuttle`)
+    pub const fn max_branches(mut self, branches: usize) -> Self {
+        self.max_branches = Some(branches);
+        self
+    }
+
+    /// Sets the maximum time spent exploring the model. (`loom` and `shuttle`)
+    pub const fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Sets the file storing the progress of the exploration, to resume it or to replay a failing
+    /// execution. (`loom-checkpoint` crate feature)
+    ///
+    /// If the file exists, the exploration resumes from the execution stored in it.
+    #[cfg(feature = "loom-checkpoint")]
+    pub const fn checkpoint_file(mut self, path: &'static str) -> Self {
+        self.checkpoint_file = Some(path);
+        self
+    }
+
+    /// Sets after how many executions the checkpoint file is written. (`loom-checkpoint` crate
+    /// feature)
+    #[cfg(feature = "loom-checkpoint")]
+    pub const fn checkpoint_interval(mut self, interval: usize) -> Self {
+        self.checkpoint_interval = Some(interval);
+        self
+    }
+
+    /// Sets the level at which iteration numbers are logged, or disables it with `None`.
+    ///
+    /// With `loom`, [`LogLevel::Trace`] also enables the execution log of the model checker.
+    pub const fn log_level(mut self, level: Option<LogLevel>) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Sets the scheduler exploring the model. (`shuttle` only)
+    pub const fn scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Sets how many schedules are explored with `shuttle` (`100` by default), or how many times
+    /// the test is run without a model (`1` by default).
+    pub const fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = Some(iterations);
+        self
+    }
+
+    #[cfg(feature = "loom")]
+    fn loom(&self) -> loom::model::Builder {
+        let mut builder = loom::model::Builder::new();
+        if self.preemption_bound.is_some() {
+            builder.preemption_bound = self.preemption_bound;
+        }
+        if let Some(max_threads) = self.max_threads {
+            builder.max_threads = max_threads;
+        }
+        if let Some(max_branches) = self.max_branches {
+            builder.max_branches = max_branches;
+        }
+        if self.max_duration.is_some() {
+            builder.max_duration = self.max_duration;
+        }
+        #[cfg(feature = "loom-checkpoint")]
+        if let Some(checkpoint_file) = self.checkpoint_file {
+            builder.checkpoint_file(checkpoint_file);
+        }
+        #[cfg(feature = "loom-checkpoint")]
+        if let Some(checkpoint_interval) = self.checkpoint_interval {
+            builder.checkpoint_interval = checkpoint_interval;
+        }
+        if self.log_level == Some(LogLevel::Trace) {
+            builder.log = true;
+        }
+        builder
+    }
+
+    #[cfg(all(feature = "shuttle", not(feature = "loom")))]
+    fn shuttle(&self) -> shuttle::Config {
+        let mut config = shuttle::Config::new();
+        if let Some(max_branches) = self.max_branches {
+            config.max_steps = shuttle::MaxSteps::FailAfter(max_branches);
+        }
+        if self.max_duration.is_some() {
+            config.max_time = self.max_duration;
+        }
+        config
+    }
+}
+
+/// Initializes the model for testing with the given configuration.
+///
+/// With `loom`, `f` is model-checked. With `shuttle`, it is run under as many schedules as set by
+/// [`ModelBuilder::iterations`], picked by the [`Scheduler`]. Otherwise, it is run as many times as
+/// set by [`ModelBuilder::iterations`].
+pub fn try_init_model_with<F>(builder: ModelBuilder, f: F)
+where
+    F: Fn() + Sync + Send + 'static
+{
+    let ModelBuilder { name, log_level, iterations, .. } = builder;
+    let name = name.unwrap_or("model");
+
+    #[cfg(feature = "loom")]
+    {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        let iteration = AtomicUsize::new(0);
+        builder.loom().check(move || {
+            let iteration = iteration.fetch_add(1, Ordering::Relaxed) + 1;
+            #[cfg(feature = "log")]
+            if let Some(level) = log_level {
+                log::log!(level.into(), "Testing {name}, iteration {iteration}...");
+            }
+            f();
+        });
+    }
+    #[cfg(all(feature = "shuttle", not(feature = "loom")))]
+    {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use shuttle::scheduler::{PctScheduler, RandomScheduler};
+
+        let iterations = iterations.unwrap_or(SHUTTLE_ITERATIONS);
+        let iteration = AtomicUsize::new(0);
+        let f = move || {
+            let iteration = iteration.fetch_add(1, Ordering::Relaxed) + 1;
+            #[cfg(feature = "log")]
+            if let Some(level) = log_level {
+                log::log!(level.into(), "Testing {name}, iteration {iteration}/{iterations}...");
+            }
+            f();
+        };
+        let config = builder.shuttle();
+        match builder.scheduler {
+            Scheduler::Random => shuttle::Runner::new(RandomScheduler::new(iterations), config).run(f),
+            Scheduler::Pct { depth } => shuttle::Runner::new(PctScheduler::new(depth, iterations), config).run(f),
+        };
+    }
+    #[cfg(not(any(feature = "loom", feature = "shuttle")))]
+    {
+        let iterations = iterations.unwrap_or(1);
+        for iteration in 1..=iterations {
+            #[cfg(feature = "log")]
+            if let Some(level) = log_level {
+                log::log!(level.into(), "Testing {name}, iteration {iteration}/{iterations}...");
+            }
+            f();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_try_init_model_with_iterations() {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        let builder = ModelBuilder::new()
+            .name("try_init_model_with_iterations")
+            .preemption_bound(2)
+            .log_level(Some(LogLevel::Info))
+            .iterations(3);
+        try_init_model_with(builder, || {
+            RUNS.fetch_add(1, Ordering::Relaxed);
+        });
+
+        #[cfg(not(feature = "loom"))]
+        assert_eq!(RUNS.load(Ordering::Relaxed), 3);
+        #[cfg(feature = "loom")]
+        assert!(RUNS.load(Ordering::Relaxed) >= 1);
+    }
+
+    #[test]
+    #[cfg(all(feature = "shuttle", not(feature = "loom")))]
+    fn test_try_init_model_with_shuttle_schedulers() {
+        use crate::prelude::*;
+        use crate::sync::thread;
+
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        for scheduler in [Scheduler::Random, Scheduler::Pct { depth: 2 }] {
+            RUNS.store(0, Ordering::Relaxed);
+            let builder = ModelBuilder::new().scheduler(scheduler).iterations(20);
+            try_init_model_with(builder, || {
+                RUNS.fetch_add(1, Ordering::Relaxed);
+                let counter: Arc<Atomic<u8>> = Arc::new(Atomic::from(0));
+                let other = thread::spawn({
+                    let counter = counter.clone();
+                    move || counter.fetch_add(1, Ordering::Relaxed)
+                });
+                counter.fetch_add(1, Ordering::Relaxed);
+                other.join().unwrap();
+                assert_eq!(counter.load(Ordering::Relaxed), 2);
+            });
+            assert_eq!(RUNS.load(Ordering::Relaxed), 20);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "shuttle", not(feature = "loom")))]
+    #[should_panic(expected = "lost update")]
+    fn test_try_init_model_with_shuttle_finds_lost_update() {
+        use crate::prelude::*;
+        use crate::sync::thread;
+
+        let builder = ModelBuilder::new().scheduler(Scheduler::Pct { depth: 2 });
+        try_init_model_with(builder, || {
+            let counter: Arc<Atomic<u8>> = Arc::new(Atomic::from(0));
+            let increment = |counter: &Atomic<u8>| {
+                let value = counter.load(Ordering::Relaxed);
+                counter.store(value + 1, Ordering::Relaxed);
+            };
+            let other = thread::spawn({
+                let counter = counter.clone();
+                move || increment(&counter)
+            });
+            increment(&counter);
+            other.join().unwrap();
+            assert_eq!(counter.load(Ordering::Relaxed), 2, "lost update");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "loom-checkpoint")]
+    fn test_try_init_model_with_checkpoint() {
+        use crate::prelude::*;
+        use crate::sync::Arc;
+        use loom::thread;
+
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        fn run(checkpoint_file: &'static str) -> usize {
+            RUNS.store(0, Ordering::Relaxed);
+            let builder = ModelBuilder::new()
+                .checkpoint_file(checkpoint_file)
+                .checkpoint_interval(1);
+            try_init_model_with(builder, || {
+                RUNS.fetch_add(1, Ordering::Relaxed);
+                let counter: Arc<Atomic<u8>> = Arc::new(Atomic::from(0));
+                let other = thread::spawn({
+                    let counter = counter.clone();
+                    move || counter.fetch_add(1, Ordering::Relaxed)
+                });
+                counter.fetch_add(1, Ordering::Relaxed);
+                other.join().unwrap();
+            });
+            RUNS.load(Ordering::Relaxed)
+        }
+
+        let path = std::env::temp_dir().join(std::format!("atomiq-checkpoint-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let checkpoint_file = std::string::String::from(path.to_str().unwrap()).leak();
+
+        let explored = run(checkpoint_file);
+        assert!(explored > 1);
+        assert!(path.exists());
+
+        // The stored execution is the last one, so resuming only replays it.
+        let resumed = run(checkpoint_file);
+        std::fs::remove_file(&path).unwrap();
+        assert!(resumed < explored);
+    }
+}
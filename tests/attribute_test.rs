@@ -0,0 +1,102 @@
+//! Tests of `#[atomiq::test]`, run by the test harness.
+//!
+//! Unlike the unit tests, these live in their own test binary: they use the attribute through
+//! the `::atomiq` path like downstream crates do, and they install a logger of their own to check
+//! the logged iteration numbers, which would conflict with the `env_logger` installed by
+//! `test_log` in the unit tests.
+#![cfg(feature = "derive")]
+
+use std::cell::Cell;
+use std::sync::{Mutex, Once};
+use log::{Log, Metadata, Record};
+
+thread_local! {
+    static RUNS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the runs on the current thread, on which the model runner calls the test body.
+fn count_run() {
+    RUNS.with(|runs| runs.set(runs.get() + 1));
+}
+
+/// Calls a test function, returning how many times it ran its body.
+fn runs_of(test: fn()) -> usize {
+    RUNS.with(|runs| runs.set(0));
+    test();
+    RUNS.with(Cell::get)
+}
+
+static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Recorder;
+
+impl Log for Recorder {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        MESSAGES.lock().unwrap().push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+fn record_logs() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        log::set_logger(&Recorder).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+    });
+}
+
+#[atomiq::test]
+fn repeated_by_default() {
+    count_run();
+}
+
+#[atomiq::test(preemption_bound = 2, max_branches = 1000, iterations = 7)]
+fn repeated_with_options() {
+    count_run();
+}
+
+#[atomiq::test(iterations = 3)]
+#[should_panic(expected = "expected panic")]
+fn forwards_should_panic() {
+    panic!("expected panic");
+}
+
+#[test]
+fn test_attribute_iterations() {
+    let default = runs_of(repeated_by_default);
+    let set = runs_of(repeated_with_options);
+
+    #[cfg(not(feature = "loom"))]
+    {
+        assert_eq!(default, 100);
+        assert_eq!(set, 7);
+    }
+    #[cfg(feature = "loom")]
+    assert!(default >= 1 && set >= 1);
+}
+
+#[test]
+#[cfg(feature = "log")]
+fn test_attribute_logs_iterations() {
+    record_logs();
+    runs_of(repeated_with_options);
+
+    let name = concat!(module_path!(), "::repeated_with_options");
+    let messages = MESSAGES.lock().unwrap();
+    let logged = messages.iter().filter(|message| message.contains(name)).count();
+
+    #[cfg(not(feature = "loom"))]
+    {
+        assert!(messages.contains(&format!("Testing {name}, iteration 1/7...")));
+        assert!(messages.contains(&format!("Testing {name}, iteration 7/7...")));
+        // The harness may run `repeated_with_options` itself while the logger is set.
+        assert!(logged >= 7);
+    }
+    #[cfg(feature = "loom")]
+    assert!(logged >= 1);
+}